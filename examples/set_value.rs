@@ -23,7 +23,8 @@ fn main() {
         "This example must be run as root"
     );
 
-    let ctl = sysctl::Ctl::new(CTLNAME).expect(&format!("could not get sysctl '{}'", CTLNAME));
+    let ctl =
+        sysctl::Ctl::new(CTLNAME).unwrap_or_else(|_| panic!("could not get sysctl '{}'", CTLNAME));
 
     let name = ctl.name().expect("could not get sysctl name");
     println!("\nFlipping value of sysctl '{}'", name);
@@ -7,29 +7,21 @@ extern crate sysctl;
 // Import the trait
 use sysctl::Sysctl;
 
-// Converted from definition in from /usr/include/sys/time.h
-#[derive(Debug)]
-#[repr(C)]
-struct ClockInfo {
-    hz: libc::c_int,   /* clock frequency */
-    tick: libc::c_int, /* micro-seconds per hz tick */
-    spare: libc::c_int,
-    stathz: libc::c_int, /* statistics clock frequency */
-    profhz: libc::c_int, /* profiling clock frequency */
-}
-
 #[cfg(any(target_os = "freebsd", target_os = "macos"))]
 fn main() {
     let ctl = sysctl::Ctl::new("kern.clockrate").expect("could not get sysctl: kern.clockrate");
 
     let name = ctl.name().expect("could not get sysctl name");
-    println!("Read sysctl {} and parse result to struct ClockInfo", name);
+    println!("Read sysctl {} and decode the struct clockinfo", name);
 
     let d = ctl.description().expect("could not get sysctl description");
     println!("Description: {:?}", d);
 
-    if let Ok(s) = ctl.value_as::<ClockInfo>() {
-        println!("{:?}", s);
+    // Well-known structs are decoded by the format of the sysctl.
+    match sysctl::StructValue::from_ctl(&ctl) {
+        Ok(sysctl::StructValue::ClockInfo(c)) => println!("{:?}\n{}", c, c),
+        Ok(s) => println!("{}", s),
+        Err(e) => println!("Could not decode: {}", e),
     }
 }
 
@@ -0,0 +1,245 @@
+// async_ctl.rs
+
+use crate::ctl_error::SysctlError;
+use crate::ctl_value::CtlValue;
+use crate::traits::Sysctl;
+
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::JoinHandle;
+
+/// The number of entries a `CtlStream` reads per blocking task.
+const BATCH: usize = 32;
+
+fn join_error(e: tokio::task::JoinError) -> SysctlError {
+    SysctlError::IoError(std::io::Error::other(e))
+}
+
+async fn blocking<F, R>(f: F) -> Result<R, SysctlError>
+where
+    F: FnOnce() -> Result<R, SysctlError> + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(join_error)?
+}
+
+/// Async versions of the `Sysctl` methods that read and write values.
+///
+/// The sysctl is accessed on tokio's blocking thread pool, so a slow read
+/// or write does not stall other tasks. Must be called from within a tokio
+/// runtime.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::{Sysctl, SysctlAsync};
+/// # async fn f() {
+/// let ctl = sysctl::Ctl::new("kernel.ostype").unwrap();
+/// println!("{}", ctl.value_string_async().await.unwrap());
+/// # }
+/// ```
+pub trait SysctlAsync: Sysctl + Clone + Send + 'static {
+    fn value_async(&self) -> impl Future<Output = Result<CtlValue, SysctlError>> + Send {
+        let ctl = self.clone();
+        blocking(move || ctl.value())
+    }
+
+    fn value_string_async(&self) -> impl Future<Output = Result<String, SysctlError>> + Send {
+        let ctl = self.clone();
+        blocking(move || ctl.value_string())
+    }
+
+    fn set_value_async(
+        &self,
+        value: CtlValue,
+    ) -> impl Future<Output = Result<CtlValue, SysctlError>> + Send {
+        let ctl = self.clone();
+        blocking(move || ctl.set_value(value))
+    }
+
+    fn set_value_string_async(
+        &self,
+        value: &str,
+    ) -> impl Future<Output = Result<String, SysctlError>> + Send {
+        let ctl = self.clone();
+        let value = value.to_owned();
+        blocking(move || ctl.set_value_string(&value))
+    }
+}
+
+impl<T: Sysctl + Clone + Send + 'static> SysctlAsync for T {}
+
+/// A `Stream` over sysctl entries.
+///
+/// Entries are read from the wrapped iterator in batches on tokio's
+/// blocking thread pool. Must be polled from within a tokio runtime.
+///
+/// A panic of the iterator is passed on to the polling task. If the
+/// blocking task is cancelled, the stream returns an error and ends.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// # async fn f() {
+/// let mut stream = sysctl::CtlStream::root();
+/// while let Some(ctl) = std::future::poll_fn(|cx| {
+///     futures_core::Stream::poll_next(std::pin::Pin::new(&mut stream), cx)
+/// })
+/// .await
+/// {
+///     println!("{}", ctl.unwrap().name().unwrap());
+/// }
+/// # }
+/// ```
+pub struct CtlStream<I: Iterator = crate::CtlIter> {
+    iter: Option<I>,
+    buffer: VecDeque<I::Item>,
+    pending: Option<JoinHandle<(I, Vec<I::Item>)>>,
+}
+
+impl CtlStream<crate::CtlIter> {
+    /// Return a stream over the complete sysctl tree.
+    pub fn root() -> Self {
+        CtlStream::new(crate::CtlIter::root())
+    }
+
+    /// Return a stream over all sysctl entries below the given node.
+    pub fn below(node: crate::Ctl) -> Self {
+        CtlStream::new(crate::CtlIter::below(node))
+    }
+}
+
+impl<I: Iterator> CtlStream<I> {
+    /// Return a stream over the entries of `iter`, such as a `CtlIter` or
+    /// the iterator of a `Backend`.
+    pub fn new(iter: I) -> Self {
+        CtlStream {
+            iter: Some(iter),
+            buffer: VecDeque::new(),
+            pending: None,
+        }
+    }
+}
+
+impl<I, C> Stream for CtlStream<I>
+where
+    I: Iterator<Item = Result<C, SysctlError>> + Send + Unpin + 'static,
+    C: Send + Unpin + 'static,
+{
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+            if self.pending.is_none() {
+                let mut iter = match self.iter.take() {
+                    Some(iter) => iter,
+                    None => return Poll::Ready(None),
+                };
+                self.pending = Some(tokio::task::spawn_blocking(move || {
+                    let items: Vec<_> = iter.by_ref().take(BATCH).collect();
+                    (iter, items)
+                }));
+            }
+
+            let pending = self.pending.as_mut().expect("pending task");
+            let result = match Pin::new(pending).poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.pending = None;
+            match result {
+                Ok((iter, items)) => {
+                    // A short batch means the iterator is exhausted.
+                    if items.len() == BATCH {
+                        self.iter = Some(iter);
+                    }
+                    if items.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    self.buffer.extend(items);
+                }
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                // The task was cancelled, such as by a runtime shutting
+                // down. The iterator is lost with it, so the stream ends.
+                Err(e) => return Poll::Ready(Some(Err(join_error(e)))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CtlStream, SysctlAsync};
+    use crate::{Backend, CtlValue, MockBackend, Sysctl};
+    use futures_core::Stream;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime")
+            .block_on(f)
+    }
+
+    async fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+        let mut items = vec![];
+        while let Some(item) =
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx)).await
+        {
+            items.push(item);
+        }
+        items
+    }
+
+    #[test]
+    fn async_value() {
+        let mock = MockBackend::new();
+        mock.insert("vm.swappiness", CtlValue::Int(60));
+        let ctl = mock.ctl("vm.swappiness").expect("ctl");
+
+        block_on(async {
+            assert_eq!(ctl.value_async().await.expect("value"), CtlValue::Int(60));
+            assert_eq!(ctl.set_value_string_async("10").await.expect("set"), "10");
+            assert_eq!(
+                ctl.set_value_async(CtlValue::Int(20)).await.expect("set"),
+                CtlValue::Int(20)
+            );
+            assert_eq!(ctl.value_string_async().await.expect("value"), "20");
+        });
+    }
+
+    #[test]
+    fn async_stream() {
+        let mock = MockBackend::new();
+        let names: Vec<String> = (0..100).map(|i| format!("a.n{:03}", i)).collect();
+        for name in &names {
+            mock.insert(name, CtlValue::Int(0));
+        }
+
+        let ctls = block_on(collect(CtlStream::new(mock.iter())));
+        let streamed: Vec<String> = ctls
+            .into_iter()
+            .map(|c| c.expect("ctl").name().expect("name"))
+            .collect();
+        assert_eq!(streamed, names);
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn async_stream_root_at() {
+        let root = crate::sys::tests::fake_root(
+            "async_stream_root_at",
+            &[("kernel/ostype", "Fake\n"), ("vm/swappiness", "60\n")],
+        );
+        let ctls = block_on(collect(CtlStream::new(crate::CtlIter::root_at(&root))));
+        let names: Vec<String> = ctls
+            .into_iter()
+            .map(|c| c.expect("ctl").name().expect("name"))
+            .collect();
+        assert_eq!(names, ["kernel.ostype", "vm.swappiness"]);
+    }
+}
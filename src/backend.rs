@@ -0,0 +1,108 @@
+// backend.rs
+
+use crate::ctl_error::SysctlError;
+use crate::traits::Sysctl;
+
+/// A source of sysctls.
+///
+/// Code written against a `Backend` instead of `Ctl` and `CtlIter` can be
+/// run against `SystemBackend` in production and against `MockBackend` in
+/// tests.
+///
+/// # Example
+/// ```
+/// # use sysctl::{Backend, Sysctl};
+/// fn ostype<B: Backend>(backend: &B) -> String {
+///     backend.ctl("kernel.ostype").and_then(|c| c.value_string()).unwrap_or_default()
+/// }
+///
+/// let mock = sysctl::MockBackend::new();
+/// mock.insert("kernel.ostype", sysctl::CtlValue::String("Linux".to_owned()));
+/// assert_eq!(ostype(&mock), "Linux");
+/// ```
+pub trait Backend {
+    /// The sysctl handles of this backend.
+    type Ctl: Sysctl;
+
+    /// The iterator returned by `iter` and `iter_below`.
+    type Iter: Iterator<Item = Result<Self::Ctl, SysctlError>>;
+
+    /// Look up the sysctl `name`.
+    fn ctl(&self, name: &str) -> Result<Self::Ctl, SysctlError>;
+
+    /// Iterate over all sysctls.
+    fn iter(&self) -> Self::Iter;
+
+    /// Iterate over the sysctls below `node`.
+    fn iter_below(&self, node: Self::Ctl) -> Self::Iter;
+
+    /// Returns the names of the nodes and sysctls directly below the node
+    /// `node`, or at the top of the tree, in order. Each name comes with
+    /// whether it is a node.
+    ///
+    /// The default implementation walks the complete subtree. Backends
+    /// that can list a single level override it.
+    fn children(&self, node: Option<&str>) -> Result<Vec<(String, bool)>, SysctlError> {
+        let (ctls, prefix) = match node {
+            Some(name) => (self.iter_below(self.ctl(name)?), format!("{}.", name)),
+            None => (self.iter(), String::new()),
+        };
+        let mut children: Vec<(String, bool)> = vec![];
+        for ctl in ctls {
+            let name = ctl?.name()?;
+            let rest = match name.strip_prefix(&prefix) {
+                Some(rest) if !rest.is_empty() => rest,
+                _ => continue,
+            };
+            let (child, is_node) = match rest.split_once('.') {
+                Some((child, _)) => (child, true),
+                None => (rest, false),
+            };
+            let child = format!("{}{}", prefix, child);
+            match children.last_mut() {
+                Some(last) if last.0 == child => last.1 |= is_node,
+                _ => children.push((child, is_node)),
+            }
+        }
+        Ok(children)
+    }
+}
+
+/// The sysctls of the running system, accessed through `Ctl` and `CtlIter`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SystemBackend;
+
+impl Backend for SystemBackend {
+    type Ctl = crate::Ctl;
+    type Iter = crate::CtlIter;
+
+    fn ctl(&self, name: &str) -> Result<Self::Ctl, SysctlError> {
+        crate::Ctl::new(name)
+    }
+
+    fn iter(&self) -> Self::Iter {
+        crate::CtlIter::root()
+    }
+
+    fn iter_below(&self, node: Self::Ctl) -> Self::Iter {
+        crate::CtlIter::below(node)
+    }
+
+    /// Lists a single directory on Linux.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn children(&self, node: Option<&str>) -> Result<Vec<(String, bool)>, SysctlError> {
+        match node {
+            Some(name) => {
+                let ctl = crate::Ctl::new(name)?;
+                crate::sys::ctl_iter::children(ctl.root(), ctl.ctl_name())
+            }
+            None => {
+                let root = crate::sys::funcs::default_root();
+                crate::sys::ctl_iter::children(
+                    root.trim_end_matches('/'),
+                    &crate::CtlName::from_path(""),
+                )
+            }
+        }
+    }
+}
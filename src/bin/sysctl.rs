@@ -0,0 +1,337 @@
+// bin/sysctl.rs
+//
+// A `sysctl` command compatible with the procps and BSD tools, built with
+// the `cli` feature.
+
+use sysctl::{Ctl, CtlFlags, CtlIter, CtlType, Sysctl, SysctlConf, SysctlError};
+
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: sysctl [options] [variable[=value] ...]
+
+Options:
+  -a, --all            display all variables
+  -n, --values         print only values of the given variables
+  -N, --names          print only variable names
+  -e, --ignore         ignore unknown variables errors
+  -q, --quiet          do not echo variable set
+  -w, --write          enable writing a value to a variable
+  -p, --load [FILE...] read values from FILE, default /etc/sysctl.conf
+      --system         read values from all system directories
+  -r, --pattern REGEX  select setting that match REGEX
+      --json           print the output as JSON
+  -h, --help           display this help and exit";
+
+const DEFAULT_CONF: &str = "/etc/sysctl.conf";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    all: bool,
+    values_only: bool,
+    names_only: bool,
+    ignore_unknown: bool,
+    quiet: bool,
+    write: bool,
+    load: bool,
+    system: bool,
+    pattern: Option<String>,
+    json: bool,
+    help: bool,
+    args: Vec<String>,
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let flags: Vec<char> = match arg.as_str() {
+            "--all" => vec!['a'],
+            "--values" => vec!['n'],
+            "--names" => vec!['N'],
+            "--ignore" => vec!['e'],
+            "--quiet" => vec!['q'],
+            "--write" => vec!['w'],
+            "--load" => vec!['p'],
+            "--pattern" => vec!['r'],
+            "--help" => vec!['h'],
+            "--system" => {
+                opts.system = true;
+                continue;
+            }
+            "--json" => {
+                opts.json = true;
+                continue;
+            }
+            "--" => {
+                opts.args.extend(args.by_ref());
+                break;
+            }
+            s if s.starts_with("--") => return Err(format!("unknown option '{}'", s)),
+            s if s.starts_with('-') && s.len() > 1 => s[1..].chars().collect(),
+            _ => {
+                opts.args.push(arg);
+                continue;
+            }
+        };
+
+        for (i, flag) in flags.iter().enumerate() {
+            match flag {
+                'a' | 'A' | 'X' => opts.all = true,
+                'n' => opts.values_only = true,
+                'N' => opts.names_only = true,
+                'e' => opts.ignore_unknown = true,
+                'q' => opts.quiet = true,
+                'w' => opts.write = true,
+                'p' | 'f' => opts.load = true,
+                'h' | '?' => opts.help = true,
+                'r' => {
+                    // The pattern is either the rest of this argument or
+                    // the next one.
+                    let rest: String = flags[i + 1..].iter().collect();
+                    opts.pattern = match rest.is_empty() {
+                        true => Some(args.next().ok_or("option requires an argument -- 'r'")?),
+                        false => Some(rest),
+                    };
+                    break;
+                }
+                c => return Err(format!("invalid option -- '{}'", c)),
+            }
+        }
+    }
+    Ok(opts)
+}
+
+/// The result of reading or writing a single sysctl.
+struct Record {
+    name: String,
+    result: Result<String, SysctlError>,
+}
+
+struct Cli {
+    opts: Options,
+    pattern: Option<regex::Regex>,
+    records: Vec<Record>,
+    failed: bool,
+}
+
+impl Cli {
+    fn selected(&self, name: &str) -> bool {
+        self.pattern.as_ref().is_none_or(|p| p.is_match(name))
+    }
+
+    fn print(&mut self, name: String, result: Result<String, SysctlError>) {
+        if let Err(e) = &result {
+            if self.opts.ignore_unknown && matches!(e, SysctlError::NotFound(_)) {
+                return;
+            }
+            self.failed = true;
+        }
+        if self.opts.json {
+            self.records.push(Record { name, result });
+            return;
+        }
+        match result {
+            Ok(_) if self.opts.names_only => println!("{}", name),
+            Ok(value) if self.opts.values_only => println!("{}", value),
+            Ok(value) => println!("{} = {}", name, value),
+            Err(e @ SysctlError::NotFound(_)) => eprintln!("sysctl: {}", e),
+            Err(e) => eprintln!("sysctl: {}: {}", name, e),
+        }
+    }
+
+    fn print_json(&self) {
+        let records: Vec<serde_json::Value> = self
+            .records
+            .iter()
+            .map(|r| match &r.result {
+                Ok(value) => serde_json::json!({ "name": r.name, "value": value }),
+                Err(e) => serde_json::json!({ "name": r.name, "error": e.to_string() }),
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(records));
+    }
+
+    // Print a single sysctl, or all sysctls below it if it is a node.
+    fn read(&mut self, name: &str) {
+        let ctl = match Ctl::new(name) {
+            Ok(ctl) => ctl,
+            Err(e) => return self.print(name.to_owned(), Err(e)),
+        };
+        match ctl.value_type() {
+            Ok(CtlType::Node) => self.read_all(ctl.into_iter()),
+            _ => self.read_one(&ctl, false),
+        }
+    }
+
+    fn read_all<I: Iterator<Item = Result<Ctl, SysctlError>>>(&mut self, ctls: I) {
+        for ctl in ctls.filter_map(Result::ok) {
+            self.read_one(&ctl, true);
+        }
+    }
+
+    fn read_one(&mut self, ctl: &Ctl, listing: bool) {
+        let name = match ctl.name() {
+            Ok(name) => name,
+            Err(e) => return self.print(String::new(), Err(e)),
+        };
+        if !self.selected(&name) {
+            return;
+        }
+        if listing {
+            // Like procps, skip what cannot be read when listing a tree.
+            match ctl.flags() {
+                Ok(flags) if flags.contains(CtlFlags::SKIP) => return,
+                Ok(flags) if !flags.contains(CtlFlags::RD) => return,
+                _ => (),
+            }
+        }
+        let value = match self.opts.names_only {
+            true => Ok(String::new()),
+            false => ctl.value_string(),
+        };
+        self.print(name, value);
+    }
+
+    fn write(&mut self, setting: &str) {
+        let (name, value) = match setting.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => {
+                eprintln!("sysctl: \"{}\" must be of the form name=value", setting);
+                self.failed = true;
+                return;
+            }
+        };
+        let result = Ctl::new(name).and_then(|ctl| ctl.set_value_string(value));
+        self.written(name.to_owned(), result);
+    }
+
+    fn written(&mut self, name: String, result: Result<String, SysctlError>) {
+        if self.opts.quiet && result.is_ok() && !self.opts.json {
+            return;
+        }
+        self.print(name, result);
+    }
+
+    fn apply(&mut self, conf: Result<SysctlConf, SysctlError>, source: &str) {
+        let conf = match conf {
+            Ok(conf) => conf,
+            Err(e) => {
+                eprintln!("sysctl: {}: {}", source, e);
+                self.failed = true;
+                return;
+            }
+        };
+        for r in conf.apply() {
+            if !self.selected(&r.name) || (r.ignore_failure && r.result.is_err()) {
+                continue;
+            }
+            self.written(r.name, r.result);
+        }
+    }
+
+    fn run(&mut self) {
+        let args = std::mem::take(&mut self.opts.args);
+
+        if self.opts.system {
+            self.apply(SysctlConf::system(), "--system");
+        } else if self.opts.load {
+            let files = match args.is_empty() {
+                true => vec![DEFAULT_CONF.to_owned()],
+                false => args,
+            };
+            for file in files {
+                self.apply(SysctlConf::from_file(&file), &file);
+            }
+        } else if self.opts.all || (args.is_empty() && self.pattern.is_some()) {
+            self.read_all(CtlIter::root());
+        } else {
+            for arg in args {
+                match self.opts.write || arg.contains('=') {
+                    true => self.write(&arg),
+                    false => self.read(&arg),
+                }
+            }
+        }
+
+        if self.opts.json {
+            self.print_json();
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("sysctl: {}\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let no_action = opts.args.is_empty() && !(opts.all || opts.load || opts.system);
+    if opts.help || (no_action && opts.pattern.is_none()) {
+        println!("{}", USAGE);
+        return match opts.help {
+            true => ExitCode::SUCCESS,
+            false => ExitCode::FAILURE,
+        };
+    }
+
+    let pattern = match opts.pattern.as_deref().map(regex::Regex::new).transpose() {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("sysctl: invalid pattern: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut cli = Cli {
+        opts,
+        pattern,
+        records: vec![],
+        failed: false,
+    };
+    cli.run();
+    match cli.failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, parse_args};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parse_args_combined() {
+        let opts = parse(&["-an", "--json", "-r", "^net\\."]).expect("parse");
+        assert!(opts.all && opts.values_only && opts.json);
+        assert_eq!(opts.pattern.as_deref(), Some("^net\\."));
+
+        let opts = parse(&["-Nr^kernel"]).expect("parse");
+        assert!(opts.names_only);
+        assert_eq!(opts.pattern.as_deref(), Some("^kernel"));
+    }
+
+    #[test]
+    fn parse_args_load() {
+        let opts = parse(&["-p", "a.conf", "b.conf"]).expect("parse");
+        assert!(opts.load);
+        assert_eq!(opts.args, ["a.conf", "b.conf"]);
+
+        let opts = parse(&["-qw", "kernel.sysrq=1"]).expect("parse");
+        assert!(opts.quiet && opts.write);
+        assert_eq!(opts.args, ["kernel.sysrq=1"]);
+    }
+
+    #[test]
+    fn parse_args_invalid() {
+        assert!(parse(&["-x"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["-r"]).is_err());
+    }
+}
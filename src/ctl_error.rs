@@ -31,6 +31,48 @@ pub enum SysctlError {
     #[error("Value is not writeable")]
     NoWriteAccess,
 
+    #[error("Invalid value for {name}: {value:?}")]
+    InvalidValue { name: String, value: String },
+
+    #[error("Permission denied: {name}")]
+    PermissionDenied { name: String, value: Option<String> },
+
+    #[error("Read-only file system, cannot set {name}")]
+    ReadOnlyFilesystem { name: String, value: String },
+
+    #[error("Invalid sysctl.conf syntax at {file}:{line}")]
+    ConfSyntax { file: String, line: usize },
+
+    #[error("Setting {name} failed, {n} sysctls not restored: {error}", n = rollback_failures.len())]
+    TransactionFailed {
+        name: String,
+        error: Box<SysctlError>,
+        rollback_failures: Vec<crate::RollbackFailure>,
+    },
+
+    #[error("Rollback failed, {n} sysctls not restored", n = .0.len())]
+    RollbackFailed(Vec<crate::RollbackFailure>),
+
+    #[error("The value of {0} cannot be restored")]
+    NotRestorable(String),
+
+    #[error("Type mismatch for {name}: {found:?} cannot be used as {expected}")]
+    TypeMismatch {
+        name: String,
+        expected: String,
+        found: crate::CtlType,
+    },
+
+    #[cfg(feature = "regex")]
+    #[error("Invalid regular expression: {0}")]
+    InvalidRegex(#[from] regex::Error),
+
+    #[error("Invalid size of {size} bytes for struct {name}")]
+    StructSize { name: String, size: usize },
+
+    #[error("Buffer of {size} bytes is too small for the value of {name}")]
+    BufferTooSmall { name: String, size: usize },
+
     #[error("Not supported by this platform")]
     NotSupported,
 
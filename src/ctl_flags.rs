@@ -4,6 +4,9 @@ use super::consts::*;
 
 // Represents control flags of a sysctl
 bitflags! {
+    /// With the `serde` feature, flags are represented by their names,
+    /// such as `"RD | WR"`.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct CtlFlags : libc::c_uint {
         /// Allow reads of variable
         const RD = CTLFLAG_RD;
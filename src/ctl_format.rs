@@ -0,0 +1,248 @@
+// ctl_format.rs
+
+use crate::ctl_type::CtlType;
+
+/// The C integer type of an integer format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntWidth {
+    /// `C`, 8 bits.
+    Char,
+    /// `S`, 16 bits.
+    Short,
+    /// `I`, 32 bits.
+    Int,
+    /// `L`, the width of a C `long` of the kernel.
+    Long,
+    /// `Q`, 64 bits.
+    Quad,
+}
+
+/// The parsed format string of a sysctl, as found in `CtlInfo::fmt`.
+///
+/// Formats defined in sysctl(9) and used by FreeBSD and macOS:
+/// * `N`                      node
+/// * `A`                      string
+/// * `C`, `S`, `I`, `L`, `Q`  signed char, short, int, long and quad
+/// * `CU`, `SU`, `IU`, `LU`, `QU`  their unsigned versions
+/// * `IK[n]`                  temperature in Kelvin, see `Temperature`
+/// * `O`                      opaque data
+/// * `S,TYPE`                 struct TYPE
+/// * `T,TYPE`                 opaque data of type TYPE, such as `dev_t`
+///
+/// # Example
+/// ```
+/// use sysctl::{CtlFormat, IntWidth};
+///
+/// assert_eq!(
+///     CtlFormat::parse("LU"),
+///     CtlFormat::Integer { width: IntWidth::Long, signed: false }
+/// );
+/// assert_eq!(
+///     CtlFormat::parse("S,clockinfo"),
+///     CtlFormat::Struct("clockinfo".to_owned())
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CtlFormat {
+    Node,
+    String,
+    Integer {
+        width: IntWidth,
+        signed: bool,
+    },
+    /// An integer holding a temperature in Kelvin multiplied by
+    /// 10^`precision`. Only the digit right after `IK` is read, and
+    /// `IK` without one is deciKelvin.
+    Temperature {
+        precision: u8,
+    },
+    Opaque,
+    Struct(String),
+    TypedOpaque(String),
+    /// A format this parser does not know, kept as is.
+    Unknown(String),
+}
+
+impl CtlFormat {
+    /// Parse a format string. Formats that are not known are returned as
+    /// `CtlFormat::Unknown`.
+    pub fn parse(fmt: &str) -> CtlFormat {
+        if let Some(name) = fmt.strip_prefix("S,") {
+            return CtlFormat::Struct(name.to_owned());
+        }
+        if let Some(name) = fmt.strip_prefix("T,") {
+            return CtlFormat::TypedOpaque(name.to_owned());
+        }
+        if let Some(precision) = fmt.strip_prefix("IK") {
+            // Only the first character is the precision. Without a digit
+            // there, the value is in deciKelvin.
+            let precision = match precision.as_bytes().first() {
+                Some(d @ b'0'..=b'9') => d - b'0',
+                _ => 1,
+            };
+            return CtlFormat::Temperature { precision };
+        }
+
+        let (width, signed) = match fmt {
+            "N" => return CtlFormat::Node,
+            "A" => return CtlFormat::String,
+            "O" => return CtlFormat::Opaque,
+            "C" => (IntWidth::Char, true),
+            "CU" => (IntWidth::Char, false),
+            "S" => (IntWidth::Short, true),
+            "SU" => (IntWidth::Short, false),
+            "I" => (IntWidth::Int, true),
+            "IU" => (IntWidth::Int, false),
+            "L" => (IntWidth::Long, true),
+            "LU" => (IntWidth::Long, false),
+            "Q" => (IntWidth::Quad, true),
+            "QU" => (IntWidth::Quad, false),
+            _ => return CtlFormat::Unknown(fmt.to_owned()),
+        };
+        CtlFormat::Integer { width, signed }
+    }
+
+    /// Return the name of the struct or opaque type, for `S,TYPE` and
+    /// `T,TYPE` formats.
+    pub fn type_name(&self) -> Option<&str> {
+        match self {
+            CtlFormat::Struct(name) | CtlFormat::TypedOpaque(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Return the `CtlType` of values in this format, if it tells.
+    pub fn ctl_type(&self) -> Option<CtlType> {
+        let ctl_type = match self {
+            CtlFormat::Node => CtlType::Node,
+            CtlFormat::String => CtlType::String,
+            CtlFormat::Struct(_) | CtlFormat::TypedOpaque(_) | CtlFormat::Opaque => CtlType::Struct,
+            CtlFormat::Integer { width, signed } => match (width, signed) {
+                (IntWidth::Char, true) => CtlType::S8,
+                (IntWidth::Char, false) => CtlType::U8,
+                (IntWidth::Short, true) => CtlType::S16,
+                (IntWidth::Short, false) => CtlType::U16,
+                (IntWidth::Int, true) => CtlType::Int,
+                (IntWidth::Int, false) => CtlType::Uint,
+                (IntWidth::Long, true) => CtlType::Long,
+                (IntWidth::Long, false) => CtlType::Ulong,
+                (IntWidth::Quad, true) => CtlType::S64,
+                (IntWidth::Quad, false) => CtlType::U64,
+            },
+            #[cfg(target_os = "freebsd")]
+            CtlFormat::Temperature { .. } => CtlType::Temperature,
+            #[cfg(not(target_os = "freebsd"))]
+            CtlFormat::Temperature { .. } => return None,
+            CtlFormat::Unknown(_) => return None,
+        };
+        Some(ctl_type)
+    }
+}
+
+impl From<&str> for CtlFormat {
+    fn from(fmt: &str) -> Self {
+        CtlFormat::parse(fmt)
+    }
+}
+
+/// Formats as the canonical format string, so `IK1` is written as `IK`.
+impl std::fmt::Display for CtlFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CtlFormat::Node => write!(f, "N"),
+            CtlFormat::String => write!(f, "A"),
+            CtlFormat::Integer { width, signed } => {
+                let c = match width {
+                    IntWidth::Char => "C",
+                    IntWidth::Short => "S",
+                    IntWidth::Int => "I",
+                    IntWidth::Long => "L",
+                    IntWidth::Quad => "Q",
+                };
+                match signed {
+                    true => write!(f, "{}", c),
+                    false => write!(f, "{}U", c),
+                }
+            }
+            CtlFormat::Temperature { precision: 1 } => write!(f, "IK"),
+            CtlFormat::Temperature { precision } => write!(f, "IK{}", precision),
+            CtlFormat::Opaque => write!(f, "O"),
+            CtlFormat::Struct(name) => write!(f, "S,{}", name),
+            CtlFormat::TypedOpaque(name) => write!(f, "T,{}", name),
+            CtlFormat::Unknown(fmt) => write!(f, "{}", fmt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CtlFormat, IntWidth};
+    use crate::{CtlInfo, CtlType};
+
+    #[test]
+    fn ctl_format_parse() {
+        let int = |width, signed| CtlFormat::Integer { width, signed };
+        let cases = [
+            ("N", CtlFormat::Node),
+            ("A", CtlFormat::String),
+            ("O", CtlFormat::Opaque),
+            ("C", int(IntWidth::Char, true)),
+            ("CU", int(IntWidth::Char, false)),
+            ("S", int(IntWidth::Short, true)),
+            ("SU", int(IntWidth::Short, false)),
+            ("I", int(IntWidth::Int, true)),
+            ("IU", int(IntWidth::Int, false)),
+            ("L", int(IntWidth::Long, true)),
+            ("LU", int(IntWidth::Long, false)),
+            ("Q", int(IntWidth::Quad, true)),
+            ("QU", int(IntWidth::Quad, false)),
+            ("IK", CtlFormat::Temperature { precision: 1 }),
+            ("IK0", CtlFormat::Temperature { precision: 0 }),
+            ("IK3", CtlFormat::Temperature { precision: 3 }),
+            ("S,clockinfo", CtlFormat::Struct("clockinfo".to_owned())),
+            ("S,", CtlFormat::Struct(String::new())),
+            ("T,dev_t", CtlFormat::TypedOpaque("dev_t".to_owned())),
+            ("", CtlFormat::Unknown(String::new())),
+            ("X", CtlFormat::Unknown("X".to_owned())),
+        ];
+        for (fmt, expected) in cases {
+            let parsed = CtlFormat::parse(fmt);
+            assert_eq!(parsed, expected, "{:?}", fmt);
+            assert_eq!(parsed.to_string(), fmt, "{:?}", fmt);
+        }
+        for (fmt, precision, canonical) in [
+            ("IK1", 1, "IK"),
+            ("IK12", 1, "IK"),
+            ("IKx", 1, "IK"),
+            ("IK35", 3, "IK3"),
+        ] {
+            let parsed = CtlFormat::parse(fmt);
+            assert_eq!(parsed, CtlFormat::Temperature { precision }, "{:?}", fmt);
+            assert_eq!(parsed.to_string(), canonical, "{:?}", fmt);
+        }
+    }
+
+    #[test]
+    fn ctl_format_types() {
+        assert_eq!(CtlFormat::parse("QU").ctl_type(), Some(CtlType::U64));
+        assert_eq!(CtlFormat::parse("SU").ctl_type(), Some(CtlType::U16));
+        assert_eq!(
+            CtlFormat::parse("S,loadavg").ctl_type(),
+            Some(CtlType::Struct)
+        );
+        assert_eq!(CtlFormat::parse("X").ctl_type(), None);
+        assert_eq!(CtlFormat::parse("S,loadavg").type_name(), Some("loadavg"));
+        assert_eq!(CtlFormat::parse("T,dev_t").type_name(), Some("dev_t"));
+        assert_eq!(CtlFormat::parse("I").type_name(), None);
+
+        let info = CtlInfo {
+            ctl_type: CtlType::Struct,
+            fmt: "S,timeval".to_owned(),
+            flags: 0,
+        };
+        assert_eq!(info.format(), CtlFormat::Struct("timeval".to_owned()));
+        assert_eq!(info.struct_type().as_deref(), Some("timeval"));
+    }
+}
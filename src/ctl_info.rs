@@ -1,9 +1,11 @@
 // ctl_info.rs
 
 use crate::ctl_flags::*;
+use crate::ctl_format::*;
 use crate::ctl_type::*;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A structure representing control metadata
 pub struct CtlInfo {
     /// The control type.
@@ -20,14 +22,16 @@ pub struct CtlInfo {
     /// * `A`       char *
     /// * `I`       int
     /// * `IK[n]`   temperature in Kelvin, multiplied by an optional single
-    ///    digit power of ten scaling factor: 1 (default) gives deciKelvin,
-    ///    0 gives Kelvin, 3 gives milliKelvin
+    ///   digit power of ten scaling factor: 1 (default) gives deciKelvin,
+    ///   0 gives Kelvin, 3 gives milliKelvin
     /// * `IU`      unsigned int
     /// * `L`       long
     /// * `LU`      unsigned long
     /// * `Q`       quad_t
     /// * `QU`      u_quad_t
     /// * `S,TYPE`  struct TYPE structures
+    ///
+    /// Use `CtlInfo::format` for the parsed format.
     pub fmt: String,
 
     pub flags: u32,
@@ -37,7 +41,7 @@ pub struct CtlInfo {
 impl CtlInfo {
     /// Is this sysctl a temperature?
     pub fn is_temperature(&self) -> bool {
-        self.fmt.starts_with("IK")
+        matches!(self.format(), CtlFormat::Temperature { .. })
     }
 }
 
@@ -47,16 +51,20 @@ impl CtlInfo {
         CtlFlags::from_bits_truncate(self.flags)
     }
 
+    /// Return the parsed format string.
+    pub fn format(&self) -> CtlFormat {
+        CtlFormat::parse(&self.fmt)
+    }
+
     /// If the sysctl is a structure, return the structure type string.
     ///
     /// Checks whether the format string starts with `S,` and returns the rest
     /// of the format string or None if the format String does not have a struct
     /// hint.
     pub fn struct_type(&self) -> Option<String> {
-        if !self.fmt.starts_with("S,") {
-            return None;
+        match self.format() {
+            CtlFormat::Struct(name) => Some(name),
+            _ => None,
         }
-
-        Some(self.fmt[2..].into())
     }
 }
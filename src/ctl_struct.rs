@@ -0,0 +1,522 @@
+// ctl_struct.rs
+
+use crate::ctl_error::SysctlError;
+use crate::ctl_value::CtlValue;
+use crate::traits::Sysctl;
+use byteorder::{ByteOrder, LittleEndian};
+
+// Return an error unless `bytes` has one of the sizes of struct `name`.
+fn check_size(name: &str, bytes: &[u8], sizes: &[usize]) -> Result<(), SysctlError> {
+    match sizes.contains(&bytes.len()) {
+        true => Ok(()),
+        false => Err(SysctlError::StructSize {
+            name: name.to_owned(),
+            size: bytes.len(),
+        }),
+    }
+}
+
+// Read a C `long` of `size` bytes.
+fn read_long(bytes: &[u8], size: usize) -> i64 {
+    match size {
+        8 => LittleEndian::read_i64(bytes),
+        _ => LittleEndian::read_i32(bytes).into(),
+    }
+}
+
+/// `struct clockinfo`, the value of `kern.clockrate`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClockInfo {
+    /// Clock frequency.
+    pub hz: i32,
+    /// Microseconds per hz tick.
+    pub tick: i32,
+    /// Unused on FreeBSD, `tickadj` on macOS.
+    pub spare: i32,
+    /// Statistics clock frequency.
+    pub stathz: i32,
+    /// Profiling clock frequency.
+    pub profhz: i32,
+}
+
+impl ClockInfo {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        check_size("clockinfo", bytes, &[20])?;
+        let mut fields = [0; 5];
+        LittleEndian::read_i32_into(bytes, &mut fields);
+        let [hz, tick, spare, stathz, profhz] = fields;
+        Ok(ClockInfo {
+            hz,
+            tick,
+            spare,
+            stathz,
+            profhz,
+        })
+    }
+}
+
+impl std::fmt::Display for ClockInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{{ hz = {}, tick = {}, profhz = {}, stathz = {} }}",
+            self.hz, self.tick, self.profhz, self.stathz
+        )
+    }
+}
+
+/// `struct loadavg`, the value of `vm.loadavg`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadAvg {
+    /// The 1, 5 and 15 minute load averages in fixed point.
+    pub ldavg: [u32; 3],
+    /// The scale of `ldavg`.
+    pub fscale: i64,
+}
+
+impl LoadAvg {
+    /// Decode a loadavg of a 64-bit (24 bytes) or 32-bit (16 bytes)
+    /// kernel.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        check_size("loadavg", bytes, &[16, 24])?;
+        let mut ldavg = [0; 3];
+        LittleEndian::read_u32_into(&bytes[..12], &mut ldavg);
+        let long = match bytes.len() {
+            24 => 8,
+            _ => 4,
+        };
+        Ok(LoadAvg {
+            ldavg,
+            fscale: read_long(&bytes[bytes.len() - long..], long),
+        })
+    }
+
+    /// Return the 1, 5 and 15 minute load averages.
+    pub fn averages(&self) -> [f64; 3] {
+        let fscale = self.fscale as f64;
+        self.ldavg.map(|l| l as f64 / fscale)
+    }
+}
+
+impl std::fmt::Display for LoadAvg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let [a, b, c] = self.averages();
+        write!(f, "{{ {:.2} {:.2} {:.2} }}", a, b, c)
+    }
+}
+
+/// `struct timeval`, the value of `kern.boottime`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timeval {
+    pub sec: i64,
+    pub usec: i64,
+}
+
+impl Timeval {
+    /// Decode a timeval of a 64-bit (16 bytes) or 32-bit (8 bytes)
+    /// kernel.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        check_size("timeval", bytes, &[8, 16])?;
+        let long = bytes.len() / 2;
+        Ok(Timeval {
+            sec: read_long(bytes, long),
+            // On macOS `tv_usec` is 32 bits followed by padding.
+            usec: LittleEndian::read_i32(&bytes[long..]).into(),
+        })
+    }
+
+    /// Return the time as a `SystemTime`, if it is after the epoch.
+    pub fn system_time(&self) -> Option<std::time::SystemTime> {
+        let since_epoch = std::time::Duration::new(
+            u64::try_from(self.sec).ok()?,
+            u32::try_from(self.usec).ok()?.checked_mul(1000)?,
+        );
+        std::time::UNIX_EPOCH.checked_add(since_epoch)
+    }
+}
+
+impl std::fmt::Display for Timeval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{{ sec = {}, usec = {} }}", self.sec, self.usec)
+    }
+}
+
+/// `struct vmtotal`, the value of `vm.vmtotal`. Memory is counted in
+/// pages. The page counts are signed, as they are `int32_t` on macOS.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmTotal {
+    /// Length of the run queue.
+    pub rq: i16,
+    /// Threads in disk wait.
+    pub dw: i16,
+    /// Threads in page wait.
+    pub pw: i16,
+    /// Threads sleeping in core.
+    pub sl: i16,
+    /// Swapped out runnable or short block threads.
+    pub sw: i16,
+    /// Total virtual memory.
+    pub vm: i64,
+    /// Active virtual memory.
+    pub avm: i64,
+    /// Total real memory in use.
+    pub rm: i64,
+    /// Active real memory.
+    pub arm: i64,
+    /// Shared virtual memory.
+    pub vmshr: i64,
+    /// Active shared virtual memory.
+    pub avmshr: i64,
+    /// Shared real memory.
+    pub rmshr: i64,
+    /// Active shared real memory.
+    pub armshr: i64,
+    /// Free memory.
+    pub free: i64,
+}
+
+impl VmTotal {
+    /// Decode a vmtotal of FreeBSD 12 or later (88 bytes) or of macOS
+    /// (48 bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        check_size("vmtotal", bytes, &[48, 88])?;
+        let mut pages = [0i64; 9];
+        let mut threads = [0i16; 5];
+        if bytes.len() == 88 {
+            // Nine 64-bit page counts, then the thread counts.
+            LittleEndian::read_i64_into(&bytes[..72], &mut pages);
+            LittleEndian::read_i16_into(&bytes[72..82], &mut threads);
+        } else {
+            // The thread counts, padding, then nine 32-bit page counts.
+            LittleEndian::read_i16_into(&bytes[..10], &mut threads);
+            for (i, p) in pages.iter_mut().enumerate() {
+                *p = LittleEndian::read_i32(&bytes[12 + 4 * i..]).into();
+            }
+        }
+        let [rq, dw, pw, sl, sw] = threads;
+        let [vm, avm, rm, arm, vmshr, avmshr, rmshr, armshr, free] = pages;
+        Ok(VmTotal {
+            rq,
+            dw,
+            pw,
+            sl,
+            sw,
+            vm,
+            avm,
+            rm,
+            arm,
+            vmshr,
+            avmshr,
+            rmshr,
+            armshr,
+            free,
+        })
+    }
+}
+
+impl std::fmt::Display for VmTotal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Processes:\t\t(RUNQ: {} Disk Wait: {} Page Wait: {} Sleep: {} Swapped: {})",
+            self.rq, self.dw, self.pw, self.sl, self.sw
+        )?;
+        writeln!(
+            f,
+            "Virtual Memory:\t\t(Total: {} pages Active: {} pages)",
+            self.vm, self.avm
+        )?;
+        writeln!(
+            f,
+            "Real Memory:\t\t(Total: {} pages Active: {} pages)",
+            self.rm, self.arm
+        )?;
+        writeln!(
+            f,
+            "Shared Virtual Memory:\t(Total: {} pages Active: {} pages)",
+            self.vmshr, self.avmshr
+        )?;
+        writeln!(
+            f,
+            "Shared Real Memory:\t(Total: {} pages Active: {} pages)",
+            self.rmshr, self.armshr
+        )?;
+        write!(f, "Free Memory:\t\t{} pages", self.free)
+    }
+}
+
+/// The page sizes supported by the hardware, the value of
+/// `hw.pagesizes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageSizes(pub Vec<u64>);
+
+impl PageSizes {
+    /// Decode an array of `u_long`. Like the other decoders, the width is
+    /// taken from the length: 64-bit if it is a multiple of 8 bytes, else
+    /// 32-bit. Unused slots, which are 0, are left out.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        let long = match bytes.len() {
+            n if n.is_multiple_of(8) => 8,
+            _ => 4,
+        };
+        if !bytes.len().is_multiple_of(long) {
+            return Err(SysctlError::StructSize {
+                name: "pagesizes".to_owned(),
+                size: bytes.len(),
+            });
+        }
+        let sizes = bytes
+            .chunks_exact(long)
+            .map(|c| match long {
+                8 => LittleEndian::read_u64(c),
+                _ => LittleEndian::read_u32(c).into(),
+            })
+            .take_while(|&s| s != 0)
+            .collect();
+        Ok(PageSizes(sizes))
+    }
+}
+
+impl std::fmt::Display for PageSizes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let sizes: Vec<String> = self.0.iter().map(|s| s.to_string()).collect();
+        write!(f, "{{ {} }}", sizes.join(", "))
+    }
+}
+
+/// A decoded struct sysctl.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let ctl = sysctl::Ctl::new("kern.clockrate").unwrap();
+/// println!("{}", sysctl::StructValue::from_ctl(&ctl).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StructValue {
+    ClockInfo(ClockInfo),
+    LoadAvg(LoadAvg),
+    Timeval(Timeval),
+    VmTotal(VmTotal),
+    PageSizes(PageSizes),
+}
+
+impl StructValue {
+    /// Decode `bytes` as the struct named `type_name`, as returned by
+    /// `CtlInfo::struct_type`. Unknown structs give
+    /// `SysctlError::MissingImplementation`.
+    pub fn decode(type_name: &str, bytes: &[u8]) -> Result<Self, SysctlError> {
+        match type_name {
+            "clockinfo" => ClockInfo::from_bytes(bytes).map(StructValue::ClockInfo),
+            "loadavg" => LoadAvg::from_bytes(bytes).map(StructValue::LoadAvg),
+            "timeval" => Timeval::from_bytes(bytes).map(StructValue::Timeval),
+            "vmtotal" => VmTotal::from_bytes(bytes).map(StructValue::VmTotal),
+            "pagesizes" => PageSizes::from_bytes(bytes).map(StructValue::PageSizes),
+            _ => Err(SysctlError::MissingImplementation),
+        }
+    }
+
+    /// Read the value of `ctl` and decode it by its struct type.
+    pub fn from_ctl<C: Sysctl>(ctl: &C) -> Result<Self, SysctlError> {
+        let type_name = ctl
+            .info()?
+            .struct_type()
+            .ok_or(SysctlError::MissingImplementation)?;
+        match ctl.value()? {
+            CtlValue::Struct(bytes) => StructValue::decode(&type_name, &bytes),
+            _ => Err(SysctlError::ExtractionError),
+        }
+    }
+}
+
+impl std::fmt::Display for StructValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StructValue::ClockInfo(v) => v.fmt(f),
+            StructValue::LoadAvg(v) => v.fmt(f),
+            StructValue::Timeval(v) => v.fmt(f),
+            StructValue::VmTotal(v) => v.fmt(f),
+            StructValue::PageSizes(v) => v.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClockInfo, LoadAvg, PageSizes, StructValue, Timeval, VmTotal};
+    use crate::{Backend, CtlType, CtlValue, MockBackend, MockEntry, SysctlError};
+
+    fn le(fields: &[(u64, usize)]) -> Vec<u8> {
+        fields
+            .iter()
+            .flat_map(|&(v, size)| v.to_le_bytes()[..size].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn struct_clockinfo() {
+        let bytes = le(&[(1000, 4), (1000, 4), (0, 4), (127, 4), (8128, 4)]);
+        let clock = ClockInfo::from_bytes(&bytes).expect("from_bytes");
+        assert_eq!(
+            clock,
+            ClockInfo {
+                hz: 1000,
+                tick: 1000,
+                spare: 0,
+                stathz: 127,
+                profhz: 8128,
+            }
+        );
+        assert_eq!(
+            clock.to_string(),
+            "{ hz = 1000, tick = 1000, profhz = 8128, stathz = 127 }"
+        );
+        assert!(matches!(
+            ClockInfo::from_bytes(&bytes[..16]),
+            Err(SysctlError::StructSize { size: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn struct_loadavg() {
+        // A 64-bit kernel pads before the long fscale.
+        let bytes = le(&[(471, 4), (594, 4), (553, 4), (0xdead, 4), (2048, 8)]);
+        let load = LoadAvg::from_bytes(&bytes).expect("from_bytes");
+        assert_eq!(load.ldavg, [471, 594, 553]);
+        assert_eq!(load.fscale, 2048);
+        assert_eq!(load.to_string(), "{ 0.23 0.29 0.27 }");
+
+        let bytes = le(&[(471, 4), (594, 4), (553, 4), (2048, 4)]);
+        assert_eq!(LoadAvg::from_bytes(&bytes).expect("from_bytes"), load);
+    }
+
+    #[test]
+    fn struct_timeval() {
+        let bytes = le(&[(1700000000, 8), (123456, 8)]);
+        let tv = Timeval::from_bytes(&bytes).expect("from_bytes");
+        assert_eq!(
+            tv,
+            Timeval {
+                sec: 1700000000,
+                usec: 123456
+            }
+        );
+        assert_eq!(tv.to_string(), "{ sec = 1700000000, usec = 123456 }");
+        assert_eq!(
+            tv.system_time(),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::new(1700000000, 123456000))
+        );
+
+        // macOS: 32-bit tv_usec followed by padding.
+        let bytes = le(&[(1700000000, 8), (123456, 4), (0xffffffff, 4)]);
+        assert_eq!(Timeval::from_bytes(&bytes).expect("from_bytes"), tv);
+
+        let bytes = le(&[(1700000000, 4), (123456, 4)]);
+        assert_eq!(Timeval::from_bytes(&bytes).expect("from_bytes"), tv);
+    }
+
+    #[test]
+    fn struct_vmtotal() {
+        let pages = [100, 50, 40, 20, 10, 5, 4, 2, 30];
+        let threads = [1, 0, 0, 50, 0];
+        let expected = VmTotal {
+            rq: 1,
+            dw: 0,
+            pw: 0,
+            sl: 50,
+            sw: 0,
+            vm: 100,
+            avm: 50,
+            rm: 40,
+            arm: 20,
+            vmshr: 10,
+            avmshr: 5,
+            rmshr: 4,
+            armshr: 2,
+            free: 30,
+        };
+
+        let mut freebsd: Vec<(u64, usize)> = pages.iter().map(|&p| (p, 8)).collect();
+        freebsd.extend(threads.iter().map(|&t| (t, 2)));
+        freebsd.extend([(0, 2), (0, 2), (0, 2)]);
+        let vm = VmTotal::from_bytes(&le(&freebsd)).expect("from_bytes");
+        assert_eq!(vm, expected);
+
+        let mut macos: Vec<(u64, usize)> = threads.iter().map(|&t| (t, 2)).collect();
+        macos.push((0, 2));
+        macos.extend(pages.iter().map(|&p| (p, 4)));
+        assert_eq!(
+            VmTotal::from_bytes(&le(&macos)).expect("from_bytes"),
+            expected
+        );
+
+        // macOS page counts are int32_t.
+        let last = macos.len() - 1;
+        macos[last] = (u32::MAX.into(), 4);
+        assert_eq!(
+            VmTotal::from_bytes(&le(&macos)).expect("from_bytes").free,
+            -1
+        );
+
+        let text = vm.to_string();
+        assert!(text.starts_with("Processes:\t\t(RUNQ: 1 Disk Wait: 0 Page Wait: 0 Sleep: 50"));
+        assert!(text.ends_with("Free Memory:\t\t30 pages"));
+    }
+
+    #[test]
+    fn struct_pagesizes() {
+        let bytes = le(&[(4096, 8), (2097152, 8), (0, 8)]);
+        let sizes = PageSizes::from_bytes(&bytes).expect("from_bytes");
+        assert_eq!(sizes, PageSizes(vec![4096, 2097152]));
+        assert_eq!(sizes.to_string(), "{ 4096, 2097152 }");
+
+        // A 32-bit kernel or compat buffer.
+        let bytes = le(&[(4096, 4), (2097152, 4), (0, 4)]);
+        assert_eq!(PageSizes::from_bytes(&bytes).expect("from_bytes"), sizes);
+
+        assert!(PageSizes::from_bytes(&bytes[..5]).is_err());
+        assert!(PageSizes::from_bytes(&[0; 3]).is_err());
+    }
+
+    #[test]
+    fn struct_from_ctl() {
+        let mock = MockBackend::new();
+        let bytes = le(&[(1000, 4), (1000, 4), (0, 4), (127, 4), (8128, 4)]);
+        mock.insert(
+            "kern.clockrate",
+            MockEntry::new(CtlValue::Struct(bytes))
+                .with_type(CtlType::Struct)
+                .with_fmt("S,clockinfo"),
+        );
+        mock.insert(
+            "kern.fake",
+            MockEntry::new(CtlValue::Struct(vec![0; 4]))
+                .with_type(CtlType::Struct)
+                .with_fmt("S,fake"),
+        );
+        mock.insert("kern.hz", CtlValue::Int(1000));
+
+        let value =
+            StructValue::from_ctl(&mock.ctl("kern.clockrate").expect("ctl")).expect("from_ctl");
+        assert!(matches!(
+            value,
+            StructValue::ClockInfo(ClockInfo { hz: 1000, .. })
+        ));
+        assert_eq!(
+            value.to_string(),
+            "{ hz = 1000, tick = 1000, profhz = 8128, stathz = 127 }"
+        );
+        for name in ["kern.fake", "kern.hz"] {
+            assert!(matches!(
+                StructValue::from_ctl(&mock.ctl(name).expect("ctl")),
+                Err(SysctlError::MissingImplementation)
+            ));
+        }
+    }
+}
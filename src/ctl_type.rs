@@ -16,7 +16,9 @@ use crate::ctl_value::*;
 /// }
 /// ```
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
+#[non_exhaustive]
 pub enum CtlType {
     Node = 1,
     Int = 2,
@@ -37,17 +39,17 @@ pub enum CtlType {
     None = 0,
     #[cfg(target_os = "freebsd")]
     Temperature = 16,
+    List = 17,
 }
 impl std::convert::From<u32> for CtlType {
     fn from(t: u32) -> Self {
-        assert!(t <= 16);
-        unsafe { std::mem::transmute(t) }
+        CtlType::from_u32(t).unwrap_or_else(|| panic!("invalid sysctl type {}", t))
     }
 }
 impl std::convert::From<&CtlValue> for CtlType {
     fn from(t: &CtlValue) -> Self {
         match t {
-            CtlValue::None => CtlType::None,
+            CtlValue::None | CtlValue::WriteOnly => CtlType::None,
             CtlValue::Node(_) => CtlType::Node,
             CtlValue::Int(_) => CtlType::Int,
             CtlValue::String(_) => CtlType::String,
@@ -63,6 +65,7 @@ impl std::convert::From<&CtlValue> for CtlType {
             CtlValue::S16(_) => CtlType::S16,
             CtlValue::S32(_) => CtlType::S32,
             CtlValue::U32(_) => CtlType::U32,
+            CtlValue::List(_) => CtlType::List,
             #[cfg(target_os = "freebsd")]
             CtlValue::Temperature(_) => CtlType::Temperature,
         }
@@ -75,6 +78,32 @@ impl std::convert::From<CtlValue> for CtlType {
 }
 
 impl CtlType {
+    /// Returns the type with the discriminant `t`, if there is one.
+    pub(crate) fn from_u32(t: u32) -> Option<Self> {
+        match t {
+            0 => Some(CtlType::None),
+            1 => Some(CtlType::Node),
+            2 => Some(CtlType::Int),
+            3 => Some(CtlType::String),
+            4 => Some(CtlType::S64),
+            5 => Some(CtlType::Struct),
+            6 => Some(CtlType::Uint),
+            7 => Some(CtlType::Long),
+            8 => Some(CtlType::Ulong),
+            9 => Some(CtlType::U64),
+            10 => Some(CtlType::U8),
+            11 => Some(CtlType::U16),
+            12 => Some(CtlType::S8),
+            13 => Some(CtlType::S16),
+            14 => Some(CtlType::S32),
+            15 => Some(CtlType::U32),
+            #[cfg(target_os = "freebsd")]
+            16 => Some(CtlType::Temperature),
+            17 => Some(CtlType::List),
+            _ => None,
+        }
+    }
+
     pub fn min_type_size(&self) -> usize {
         match self {
             CtlType::None => 0,
@@ -94,6 +123,7 @@ impl CtlType {
             CtlType::S32 => std::mem::size_of::<i32>(),
             CtlType::U32 => std::mem::size_of::<u32>(),
             // Added custom types below
+            CtlType::List => 0,
             #[cfg(target_os = "freebsd")]
             CtlType::Temperature => 0,
         }
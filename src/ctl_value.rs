@@ -19,7 +19,13 @@ use enum_as_inner::EnumAsInner;
 ///     }
 /// }
 /// ```
-#[derive(Debug, EnumAsInner, PartialEq, PartialOrd)]
+///
+/// With the `serde` feature, values are represented with their type and
+/// value, such as `{"type": "Int", "value": 1}`.
+#[derive(Debug, Clone, EnumAsInner, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+#[non_exhaustive]
 pub enum CtlValue {
     None,
     Node(Vec<u8>),
@@ -37,6 +43,12 @@ pub enum CtlValue {
     S16(i16),
     S32(i32),
     U32(u32),
+    /// A value made up of several fields, such as `net.ipv4.tcp_rmem`
+    /// on Linux.
+    List(Vec<CtlValue>),
+    /// The result of writing a write-only sysctl, such as `vm.drop_caches`
+    /// on Linux: the value was written but cannot be read back.
+    WriteOnly,
     #[cfg(target_os = "freebsd")]
     Temperature(Temperature),
 }
@@ -45,6 +57,7 @@ impl std::fmt::Display for CtlValue {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let s = match self {
             CtlValue::None => "[None]".to_owned(),
+            CtlValue::WriteOnly => "[Write-only]".to_owned(),
             CtlValue::Int(i) => format!("{}", i),
             CtlValue::Uint(i) => format!("{}", i),
             CtlValue::Long(i) => format!("{}", i),
@@ -60,6 +73,11 @@ impl std::fmt::Display for CtlValue {
             CtlValue::Struct(_) => "[Opaque Struct]".to_owned(),
             CtlValue::Node(_) => "[Node]".to_owned(),
             CtlValue::String(s) => s.to_owned(),
+            CtlValue::List(l) => l
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join("\t"),
             #[cfg(target_os = "freebsd")]
             CtlValue::Temperature(t) => format!("{}", t.kelvin()),
         };
@@ -180,3 +198,57 @@ mod tests_unix {
         assert_eq!(info.struct_type(), None);
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests_serde {
+    use crate::{CtlFlags, CtlInfo, CtlType, CtlValue};
+
+    #[test]
+    fn ctl_value_serde() {
+        let values = [
+            CtlValue::None,
+            CtlValue::WriteOnly,
+            CtlValue::Node(vec![1, 2]),
+            CtlValue::Int(i32::MIN),
+            CtlValue::String("4\t4\t1\t7".to_owned()),
+            CtlValue::S64(i64::MIN),
+            CtlValue::Struct(vec![0, 255]),
+            CtlValue::Uint(u32::MAX),
+            CtlValue::Long(i64::MAX),
+            CtlValue::Ulong(u64::MAX),
+            CtlValue::U64(u64::MAX),
+            CtlValue::U8(u8::MAX),
+            CtlValue::U16(u16::MAX),
+            CtlValue::S8(i8::MIN),
+            CtlValue::S16(i16::MIN),
+            CtlValue::S32(i32::MIN),
+            CtlValue::U32(u32::MAX),
+            CtlValue::List(vec![CtlValue::Int(4096), CtlValue::Ulong(u64::MAX)]),
+        ];
+        for value in values {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<CtlValue>(&json).unwrap(), value);
+        }
+        assert_eq!(
+            serde_json::to_string(&CtlValue::Int(1)).unwrap(),
+            r#"{"type":"Int","value":1}"#
+        );
+    }
+
+    #[test]
+    fn ctl_info_serde() {
+        let info = CtlInfo {
+            ctl_type: CtlType::List,
+            fmt: "S,clockinfo".to_owned(),
+            flags: (CtlFlags::RW | CtlFlags::ANYBODY).bits() | 0x1,
+        };
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<CtlInfo>(&json).unwrap(), info);
+
+        let flags = CtlFlags::from_bits_retain(info.flags);
+        let json = serde_json::to_string(&flags).unwrap();
+        let parsed: CtlFlags = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.bits(), flags.bits());
+        assert_eq!(serde_json::to_string(&CtlFlags::RD).unwrap(), r#""RD""#);
+    }
+}
@@ -0,0 +1,154 @@
+// group.rs
+
+use crate::ctl_error::SysctlError;
+
+/// A field of a `#[derive(Sysctl)]` struct that could not be read or
+/// applied.
+#[derive(Debug)]
+pub struct FieldError {
+    /// The name of the struct field.
+    pub field: &'static str,
+
+    /// The name of the sysctl bound to the field.
+    pub name: &'static str,
+
+    pub error: SysctlError,
+}
+
+/// The error returned by the methods generated by `#[derive(Sysctl)]`,
+/// holding an error for each field that failed.
+///
+/// # Example
+/// ```no_run
+/// #[derive(sysctl::Sysctl)]
+/// struct TcpSettings {
+///     #[sysctl(name = "net.ipv4.tcp_syncookies")]
+///     syncookies: bool,
+///     #[sysctl(name = "net.ipv4.tcp_rmem")]
+///     rmem: [u32; 3],
+///     #[sysctl(name = "net.ipv4.tcp_fin_timeout")]
+///     fin_timeout: u32,
+/// }
+///
+/// let mut tcp = TcpSettings::read().unwrap();
+/// tcp.fin_timeout = 30;
+/// if let Err(e) = tcp.apply() {
+///     for f in e.errors {
+///         eprintln!("{} ({}): {}", f.field, f.name, f.error);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct GroupError {
+    pub errors: Vec<FieldError>,
+}
+
+impl GroupError {
+    /// Returns the value, or records the error for `field` and returns
+    /// None.
+    #[doc(hidden)]
+    pub fn record<T>(
+        errors: &mut Vec<FieldError>,
+        field: &'static str,
+        name: &'static str,
+        result: Result<T, SysctlError>,
+    ) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                errors.push(FieldError { field, name, error });
+                None
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for GroupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let fields: Vec<String> = self
+            .errors
+            .iter()
+            .map(|e| format!("{} ({}): {}", e.field, e.name, e.error))
+            .collect();
+        write!(f, "{}", fields.join(", "))
+    }
+}
+
+impl std::error::Error for GroupError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CtlFlags, CtlValue, MockBackend, MockEntry, SysctlError};
+
+    #[derive(Debug, PartialEq, crate::Sysctl)]
+    struct TcpSettings {
+        #[sysctl(name = "net.ipv4.tcp_syncookies")]
+        syncookies: bool,
+        #[sysctl(name = "net.ipv4.tcp_rmem")]
+        rmem: [u32; 3],
+        #[sysctl(name = "net.ipv4.tcp_fin_timeout")]
+        fin_timeout: u32,
+    }
+
+    fn tcp_mock() -> MockBackend {
+        let mock = MockBackend::new();
+        mock.insert("net.ipv4.tcp_syncookies", CtlValue::String("1".to_owned()));
+        mock.insert(
+            "net.ipv4.tcp_rmem",
+            CtlValue::String("4096\t131072\t6291456".to_owned()),
+        );
+        mock.insert(
+            "net.ipv4.tcp_fin_timeout",
+            CtlValue::String("60".to_owned()),
+        );
+        mock
+    }
+
+    #[test]
+    fn group_read_apply() {
+        let mock = tcp_mock();
+        let mut tcp = TcpSettings::read_from(&mock).expect("read_from");
+        assert_eq!(
+            tcp,
+            TcpSettings {
+                syncookies: true,
+                rmem: [4096, 131072, 6291456],
+                fin_timeout: 60,
+            }
+        );
+
+        tcp.syncookies = false;
+        tcp.rmem[0] = 8192;
+        tcp.apply_to(&mock).expect("apply_to");
+        assert_eq!(
+            mock.get("net.ipv4.tcp_rmem"),
+            Some(CtlValue::String("8192\t131072\t6291456".to_owned()))
+        );
+        assert_eq!(TcpSettings::read_from(&mock).expect("read_from"), tcp);
+    }
+
+    #[test]
+    fn group_errors() {
+        let mock = tcp_mock();
+        mock.remove("net.ipv4.tcp_syncookies");
+        mock.insert("net.ipv4.tcp_rmem", CtlValue::String("4096".to_owned()));
+        let e = TcpSettings::read_from(&mock).expect_err("read_from");
+        let fields: Vec<&str> = e.errors.iter().map(|f| f.field).collect();
+        assert_eq!(fields, ["syncookies", "rmem"]);
+        assert!(matches!(e.errors[0].error, SysctlError::NotFound(_)));
+
+        let mock = tcp_mock();
+        mock.insert(
+            "net.ipv4.tcp_fin_timeout",
+            MockEntry::new(CtlValue::String("60".to_owned())).with_flags(CtlFlags::RD),
+        );
+        let tcp = TcpSettings::read_from(&mock).expect("read_from");
+        let e = tcp.apply_to(&mock).expect_err("apply_to");
+        assert_eq!(e.errors.len(), 1);
+        assert_eq!(e.errors[0].name, "net.ipv4.tcp_fin_timeout");
+        assert!(
+            e.to_string()
+                .starts_with("fin_timeout (net.ipv4.tcp_fin_timeout): ")
+        );
+    }
+}
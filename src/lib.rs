@@ -15,8 +15,8 @@
 //! println!("Description: {}", desc);
 //! let val = ctl.value().unwrap();
 //! println!("Value: {}", val);
-//! // On Linux all sysctls are String type. Use the following for
-//! // cross-platform compatibility:
+//! // On Linux all sysctls are String type, unless the Ctl was created with
+//! // `Ctl::new_typed`. Use the following for cross-platform compatibility:
 //! let str_val = ctl.value_string().unwrap();
 //! println!("String value: {}", str_val);
 //! ```
@@ -47,6 +47,11 @@ extern crate enum_as_inner;
 extern crate libc;
 extern crate thiserror;
 
+// Lets the code generated by `#[derive(Sysctl)]` refer to `::sysctl` in
+// this crate's own tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as sysctl;
+
 #[cfg(any(target_os = "android", target_os = "linux"))]
 extern crate walkdir;
 
@@ -58,24 +63,64 @@ mod sys;
 #[path = "unix/mod.rs"]
 mod sys;
 
+#[cfg(feature = "async")]
+mod async_ctl;
+mod backend;
 mod consts;
 mod ctl_error;
 mod ctl_flags;
+mod ctl_format;
 mod ctl_info;
+mod ctl_struct;
 mod ctl_type;
 mod ctl_value;
+#[cfg(feature = "derive")]
+mod group;
+mod metrics;
+mod mock;
+mod selector;
+mod snapshot;
+mod sysctl_conf;
 #[cfg(target_os = "freebsd")]
 mod temperature;
 mod traits;
+mod transaction;
+mod typed;
+mod watcher;
 
+#[cfg(feature = "async")]
+pub use async_ctl::*;
+pub use backend::*;
 pub use consts::*;
 pub use ctl_error::*;
 pub use ctl_flags::*;
+pub use ctl_format::*;
 pub use ctl_info::*;
+pub use ctl_struct::*;
 pub use ctl_type::*;
 pub use ctl_value::*;
+#[cfg(feature = "derive")]
+pub use group::*;
+pub use metrics::*;
+pub use mock::*;
+pub use selector::*;
+pub use snapshot::*;
 pub use sys::ctl::*;
 pub use sys::ctl_iter::*;
+pub use sysctl_conf::*;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use sys::ctl_name::CtlName;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use sys::dump::Dump;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use sys::handle::{CtlHandle, Sampler};
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use sys::netns::Netns;
 #[cfg(target_os = "freebsd")]
 pub use temperature::Temperature;
 pub use traits::Sysctl;
+#[cfg(feature = "derive")]
+pub use sysctl_derive::Sysctl;
+pub use transaction::*;
+pub use typed::*;
+pub use watcher::*;
@@ -1,7 +1,8 @@
 // linux/ctl.rs
 
-use super::funcs::{path_to_name, set_value, value};
-use crate::consts::*;
+use super::ctl_name::CtlName;
+use super::funcs::{access_flags, default_root, infer_value, set_value, value, value_typed};
+use super::netns::Netns;
 use crate::ctl_error::SysctlError;
 use crate::ctl_flags::CtlFlags;
 use crate::ctl_info::CtlInfo;
@@ -9,30 +10,206 @@ use crate::ctl_type::CtlType;
 use crate::ctl_value::CtlValue;
 use crate::traits::Sysctl;
 
+use std::os::unix::fs::PermissionsExt;
 use std::str::FromStr;
 
 /// This struct represents a system control.
+///
+/// Sysctls are read from `/proc/sys`, or from the directory named by the
+/// `SYSCTL_ROOT` environment variable if it is set. Use `Ctl::new_in` to
+/// read them from another directory, or `Ctl::new_in_netns` to access them
+/// in another network namespace.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Ctl {
-    name: String,
+    root: String,
+    name: CtlName,
+    typed: bool,
+    netns: Option<Netns>,
 }
 
 impl FromStr for Ctl {
     type Err = SysctlError;
 
     fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ctl::new_in(&default_root(), name)
+    }
+}
+
+impl Ctl {
+    /// Construct a Ctl for a sysctl tree rooted at `root` instead of
+    /// `/proc/sys`, such as `/proc/<pid>/root/proc/sys` or a copy of the
+    /// tree used in tests.
+    ///
+    /// The name is parsed as described for `CtlName`. A path below `root`
+    /// is accepted as well.
+    ///
+    /// # Example
+    /// ```
+    /// let ctl = sysctl::Ctl::new_in("/proc/1/root/proc/sys", "kernel.ostype");
+    /// ```
+    pub fn new_in(root: &str, name: &str) -> Result<Self, SysctlError> {
+        Ctl::lookup(root, name, None)
+    }
+
+    /// Construct a Ctl that is read and written in the network namespace
+    /// `netns`.
+    ///
+    /// Iterating over the returned Ctl yields Ctls in the same namespace.
+    pub fn new_in_netns(netns: &Netns, name: &str) -> Result<Self, SysctlError> {
+        Ctl::lookup(&default_root(), name, Some(netns.clone()))
+    }
+
+    pub(crate) fn lookup(
+        root: &str,
+        name: &str,
+        netns: Option<Netns>,
+    ) -> Result<Self, SysctlError> {
+        let root = root.trim_end_matches('/');
+        let ctl_name = match name.strip_prefix(root).and_then(|n| n.strip_prefix('/')) {
+            Some(path) => CtlName::from_path(path),
+            None => CtlName::from(name),
+        };
+        Ctl::open(root, ctl_name, netns).map_err(|_| SysctlError::NotFound(name.to_owned()))
+    }
+
+    /// Construct a Ctl from a path, either absolute or relative to the
+    /// sysctl root. Unlike `Ctl::new`, dots in the path are never treated
+    /// as separators.
+    ///
+    /// # Example
+    /// ```
+    /// # use sysctl::Sysctl;
+    /// let ctl = sysctl::Ctl::from_path("/proc/sys/kernel/ostype").unwrap();
+    /// assert_eq!(ctl.name().unwrap(), "kernel.ostype");
+    /// ```
+    pub fn from_path(path: &str) -> Result<Self, SysctlError> {
+        let root = default_root();
+        let root = root.trim_end_matches('/');
+        let rel = path
+            .strip_prefix(root)
+            .and_then(|p| p.strip_prefix('/'))
+            .unwrap_or(path);
+        Ctl::open(root, CtlName::from_path(rel), None)
+            .map_err(|_| SysctlError::NotFound(path.to_owned()))
+    }
+
+    fn open(root: &str, name: CtlName, netns: Option<Netns>) -> Result<Self, SysctlError> {
         let ctl = Ctl {
-            name: path_to_name(name),
+            root: root.to_owned(),
+            name,
+            typed: false,
+            netns,
         };
-        let _ =
-            std::fs::File::open(ctl.path()).map_err(|_| SysctlError::NotFound(name.to_owned()))?;
+        let path = ctl.path();
+        // Write-only sysctls cannot be opened for reading, even by root.
+        ctl.run(move || Ok(std::fs::metadata(path).map(|_| ())?))?;
         Ok(ctl)
     }
-}
 
-impl Ctl {
+    /// Returns the network namespace this Ctl is bound to, if any.
+    pub fn netns(&self) -> Option<&Netns> {
+        self.netns.as_ref()
+    }
+
+    // Run `f` in the network namespace of this Ctl.
+    pub(crate) fn run<F, R>(&self, f: F) -> Result<R, SysctlError>
+    where
+        F: FnOnce() -> Result<R, SysctlError> + Send + 'static,
+        R: Send + 'static,
+    {
+        match &self.netns {
+            Some(netns) => netns.run(f)?,
+            None => f(),
+        }
+    }
+
+    /// Returns the root of the sysctl tree this Ctl belongs to.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Returns the structured name of this Ctl.
+    pub fn ctl_name(&self) -> &CtlName {
+        &self.name
+    }
+
     pub fn path(&self) -> String {
-        format!("/proc/sys/{}", self.name.replace(".", "/"))
+        format!("{}/{}", self.root, self.name.to_path())
+    }
+
+    /// Construct a Ctl that infers the type of its value.
+    ///
+    /// All sysctls are strings on Linux. A Ctl constructed with this
+    /// function returns single integers as `CtlValue::Int`, `Uint`, `Long`
+    /// or `Ulong` from `value()`, several fields such as `net.ipv4.tcp_rmem`
+    /// as a `CtlValue::List` of typed fields, and reports the inferred type from
+    /// `value_type()` and `info()`, which matches what the other platforms
+    /// return for numeric sysctls.
+    ///
+    /// # Example
+    /// ```
+    /// # use sysctl::Sysctl;
+    /// let ctl = sysctl::Ctl::new_typed("kernel.pid_max").unwrap();
+    /// assert!(ctl.value().unwrap().as_int().is_some());
+    /// ```
+    pub fn new_typed(name: &str) -> Result<Self, SysctlError> {
+        Ok(Ctl::from_str(name)?.with_typed(true))
+    }
+
+    /// Set whether this Ctl infers the type of its value, as Ctls from
+    /// `new_typed` do. Use it to get a typed Ctl from the other
+    /// constructors.
+    ///
+    /// # Example
+    /// ```
+    /// # use sysctl::Sysctl;
+    /// let ctl = sysctl::Ctl::from_path("/proc/sys/kernel/pid_max")
+    ///     .unwrap()
+    ///     .with_typed(true);
+    /// assert!(ctl.value().unwrap().as_int().is_some());
+    /// ```
+    pub fn with_typed(mut self, typed: bool) -> Self {
+        self.typed = typed;
+        self
+    }
+
+    /// Returns true if this Ctl infers the type of its value.
+    pub fn is_typed(&self) -> bool {
+        self.typed
+    }
+
+    /// Returns the value with its type inferred, regardless of whether
+    /// the Ctl was constructed with `new_typed`.
+    pub fn value_typed(&self) -> Result<CtlValue, SysctlError> {
+        let path = self.path();
+        self.run(move || value_typed(&path))
+            .map_err(|e| self.named(e))
+    }
+
+    // Errors from `funcs` refer to the sysctl by its path.
+    pub(crate) fn named(&self, e: SysctlError) -> SysctlError {
+        let name = self.name.to_string();
+        match e {
+            SysctlError::NotFound(_) => SysctlError::NotFound(name),
+            SysctlError::InvalidValue { value, .. } => SysctlError::InvalidValue { name, value },
+            SysctlError::PermissionDenied { value, .. } => {
+                SysctlError::PermissionDenied { name, value }
+            }
+            SysctlError::ReadOnlyFilesystem { value, .. } => {
+                SysctlError::ReadOnlyFilesystem { name, value }
+            }
+            e => e,
+        }
+    }
+
+    // Write-only sysctls cannot be read to infer a type, so they are
+    // strings like they are for untyped Ctls.
+    fn inferred_type(&self) -> Result<CtlType, SysctlError> {
+        match self.value_typed() {
+            Ok(v) => Ok(CtlType::from(&v)),
+            Err(SysctlError::NoReadAccess) => Ok(CtlType::String),
+            Err(e) => Err(e),
+        }
     }
 }
 
@@ -46,13 +223,16 @@ impl Sysctl for Ctl {
     }
 
     fn name(&self) -> Result<String, SysctlError> {
-        Ok(self.name.clone())
+        Ok(self.name.to_string())
     }
 
     fn value_type(&self) -> Result<CtlType, SysctlError> {
-        let md = std::fs::metadata(&self.path()).map_err(SysctlError::IoError)?;
-        if md.is_dir() {
+        let path = self.path();
+        let is_dir = self.run(move || Ok(std::fs::metadata(path)?.is_dir()))?;
+        if is_dir {
             Ok(CtlType::Node)
+        } else if self.typed {
+            self.inferred_type()
         } else {
             Ok(CtlType::String)
         }
@@ -63,7 +243,12 @@ impl Sysctl for Ctl {
     }
 
     fn value(&self) -> Result<CtlValue, SysctlError> {
-        value(&self.path())
+        if self.typed {
+            self.value_typed()
+        } else {
+            let path = self.path();
+            self.run(move || value(&path)).map_err(|e| self.named(e))
+        }
     }
 
     fn value_string(&self) -> Result<String, SysctlError> {
@@ -75,30 +260,50 @@ impl Sysctl for Ctl {
     }
 
     fn set_value(&self, value: CtlValue) -> Result<CtlValue, SysctlError> {
-        set_value(&self.path(), value)
+        let path = self.path();
+        match self
+            .run(move || set_value(&path, value))
+            .map_err(|e| self.named(e))?
+        {
+            CtlValue::String(s) if self.typed => Ok(infer_value(&s)),
+            v => Ok(v),
+        }
     }
 
+    /// Returns `"[Write-only]"`, the string of `CtlValue::WriteOnly`, for
+    /// write-only sysctls.
     fn set_value_string(&self, value: &str) -> Result<String, SysctlError> {
-        self.set_value(CtlValue::String(value.to_owned()))?;
-        self.value_string()
+        match self.set_value(CtlValue::String(value.to_owned()))? {
+            v @ CtlValue::WriteOnly => Ok(v.to_string()),
+            _ => self.value_string(),
+        }
     }
 
     fn flags(&self) -> Result<CtlFlags, SysctlError> {
         Ok(self.info()?.flags())
     }
 
+    fn is_writable(&self) -> Result<bool, SysctlError> {
+        let path = self.path();
+        let mode = self.run(move || Ok(std::fs::metadata(path)?.permissions().mode()))?;
+        Ok(mode & 0o222 != 0)
+    }
+
+    /// The `RD` and `WR` flags tell whether the calling process may read
+    /// and write the sysctl, taking its effective IDs and capabilities into
+    /// account. `ANYBODY` is set for world-writable sysctls.
     fn info(&self) -> Result<CtlInfo, SysctlError> {
-        let md = std::fs::metadata(&self.path()).map_err(SysctlError::IoError)?;
-        let mut flags = 0;
-        if md.permissions().readonly() {
-            flags |= CTLFLAG_RD;
+        let path = self.path();
+        let flags = self.run(move || access_flags(&path))?;
+        let ctl_type = if self.typed {
+            self.value_type()?
         } else {
-            flags |= CTLFLAG_RW;
-        }
+            CtlType::String
+        };
         let s = CtlInfo {
-            ctl_type: CtlType::String,
+            ctl_type,
             fmt: "".to_owned(),
-            flags: flags,
+            flags,
         };
         Ok(s)
     }
@@ -112,4 +317,164 @@ mod tests {
     fn ctl_new() {
         let _ = super::Ctl::new("kernel.ostype").expect("Ctl::new");
     }
+
+    #[test]
+    fn ctl_new_in() {
+        let root = crate::sys::tests::fake_root(
+            "ctl_new_in",
+            &[("kernel/ostype", "Fake\n"), ("net/ipv4/ip_forward", "0\n")],
+        );
+
+        let ctl = super::Ctl::new_in(&root, "kernel.ostype").expect("Ctl::new_in");
+        assert_eq!(ctl.root(), root);
+        assert_eq!(ctl.path(), format!("{}/kernel/ostype", root));
+        assert_eq!(ctl.value_string().expect("value_string"), "Fake");
+
+        let ctl = super::Ctl::new_in(&root, &format!("{}/net/ipv4/ip_forward", root))
+            .expect("Ctl::new_in");
+        assert_eq!(ctl.name().expect("name"), "net.ipv4.ip_forward");
+        assert_eq!(ctl.set_value_string("1").expect("set_value_string"), "1");
+
+        assert!(super::Ctl::new_in(&root, "kernel.version").is_err());
+    }
+
+    #[test]
+    fn ctl_name_with_dots() {
+        let root = crate::sys::tests::fake_root(
+            "ctl_name_with_dots",
+            &[("net/ipv4/conf/eth0.100/rp_filter", "1\n")],
+        );
+
+        for name in [
+            "net.ipv4.conf.eth0/100.rp_filter",
+            "net/ipv4/conf/eth0.100/rp_filter",
+            &format!("{}/net/ipv4/conf/eth0.100/rp_filter", root),
+        ] {
+            let ctl = super::Ctl::new_in(&root, name).expect("Ctl::new_in");
+            assert_eq!(
+                ctl.name().expect("name"),
+                "net.ipv4.conf.eth0/100.rp_filter"
+            );
+            assert_eq!(
+                ctl.path(),
+                format!("{}/net/ipv4/conf/eth0.100/rp_filter", root)
+            );
+        }
+
+        assert!(super::Ctl::new_in(&root, "net.ipv4.conf.eth0.100.rp_filter").is_err());
+
+        for ctl in crate::CtlIter::root_at(&root) {
+            let name = ctl.expect("ctl").name().expect("name");
+            let ctl = super::Ctl::new_in(&root, &name).expect("round trip");
+            assert_eq!(ctl.value_string().expect("value_string"), "1");
+        }
+    }
+
+    #[test]
+    fn ctl_write_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = crate::sys::tests::fake_root("ctl_write_only", &[("vm/drop_caches", "")]);
+        let ctl = super::Ctl::new_in(&root, "vm.drop_caches").expect("Ctl::new_in");
+        std::fs::set_permissions(ctl.path(), std::fs::Permissions::from_mode(0o200))
+            .expect("set_permissions");
+
+        let flags = ctl.flags().expect("flags");
+        assert!(flags.contains(crate::CtlFlags::WR));
+        assert!(!flags.contains(crate::CtlFlags::RD));
+        assert_eq!(
+            ctl.set_value(crate::CtlValue::String("3".into()))
+                .expect("set_value"),
+            crate::CtlValue::WriteOnly
+        );
+        assert_eq!(
+            ctl.set_value_string("3").expect("set_value_string"),
+            "[Write-only]"
+        );
+    }
+
+    #[test]
+    fn ctl_flags_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = crate::sys::tests::fake_root(
+            "ctl_flags_mode",
+            &[("kernel/ostype", "Fake\n"), ("kernel/anybody", "0\n")],
+        );
+
+        let ctl = super::Ctl::new_in(&root, "kernel.ostype").expect("Ctl::new_in");
+        std::fs::set_permissions(ctl.path(), std::fs::Permissions::from_mode(0o444))
+            .expect("set_permissions");
+        assert_eq!(ctl.flags().expect("flags").bits(), crate::CTLFLAG_RD);
+
+        let ctl = super::Ctl::new_in(&root, "kernel.anybody").expect("Ctl::new_in");
+        std::fs::set_permissions(ctl.path(), std::fs::Permissions::from_mode(0o666))
+            .expect("set_permissions");
+        assert_eq!(
+            ctl.flags().expect("flags").bits(),
+            crate::CTLFLAG_RW | crate::CTLFLAG_ANYBODY
+        );
+    }
+
+    #[test]
+    fn ctl_set_value_permission_denied() {
+        let ctl = super::Ctl::new("kernel.ostype").expect("Ctl::new");
+        let r = ctl.set_value_string("Fake");
+        assert!(matches!(
+            r,
+            Err(crate::SysctlError::PermissionDenied { name, value: Some(v) })
+                if name == "kernel.ostype" && v == "Fake"
+        ));
+    }
+
+    #[test]
+    fn ctl_from_path() {
+        let ctl = super::Ctl::from_path("/proc/sys/kernel/ostype").expect("Ctl::from_path");
+        assert_eq!(ctl.name().expect("name"), "kernel.ostype");
+        let ctl = super::Ctl::from_path("kernel/ostype").expect("Ctl::from_path");
+        assert_eq!(ctl.name().expect("name"), "kernel.ostype");
+        assert!(super::Ctl::from_path("kernel.ostype").is_err());
+    }
+
+    #[test]
+    fn ctl_new_typed() {
+        let ctl = super::Ctl::new_typed("kernel.pid_max").expect("Ctl::new_typed");
+        assert!(ctl.is_typed());
+        assert!(ctl.value().expect("value").as_int().is_some());
+        assert_eq!(ctl.value_type().expect("value_type"), crate::CtlType::Int);
+        assert_eq!(ctl.info().expect("info").ctl_type, crate::CtlType::Int);
+
+        let ctl = super::Ctl::new_typed("kernel.ostype").expect("Ctl::new_typed");
+        assert_eq!(
+            ctl.value_type().expect("value_type"),
+            crate::CtlType::String
+        );
+
+        let ctl = super::Ctl::new("kernel.pid_max").expect("Ctl::new");
+        assert!(!ctl.is_typed());
+        assert_eq!(
+            ctl.value_type().expect("value_type"),
+            crate::CtlType::String
+        );
+    }
+
+    #[test]
+    fn ctl_with_typed() {
+        let root = crate::sys::tests::fake_root(
+            "ctl_with_typed",
+            &[("kernel/pid_max", "4194304\n"), ("kernel/broken", "0\n")],
+        );
+        let ctl = super::Ctl::new_in(&root, "kernel.pid_max")
+            .expect("Ctl::new_in")
+            .with_typed(true);
+        assert_eq!(ctl.value().expect("value"), crate::CtlValue::Int(4194304));
+        assert_eq!(ctl.value_type().expect("value_type"), crate::CtlType::Int);
+
+        // Read errors are not hidden behind CtlType::String.
+        let ctl = super::Ctl::new_in(&root, "kernel.broken")
+            .expect("Ctl::new_in")
+            .with_typed(true);
+        std::fs::write(ctl.path(), [0xff, 0xfe]).expect("write");
+        assert!(ctl.value_type().is_err());
+    }
 }
@@ -1,48 +1,98 @@
 // linux/ctl_iter.rs
 
 use super::ctl::Ctl;
+use super::ctl_name::CtlName;
+use super::funcs::{default_root, read_error};
+use super::netns::Netns;
 use crate::ctl_error::SysctlError;
-use crate::traits::Sysctl;
 
 /// An iterator over Sysctl entries.
+///
+/// The tree is walked lazily, one directory at a time, in the same sorted
+/// order as `sysctl -a`.
 pub struct CtlIter {
-    direntries: Vec<walkdir::DirEntry>,
-    base: String,
-    cur_idx: usize,
+    walker: Option<walkdir::IntoIter>,
+    root: String,
+    typed: bool,
+    netns: Option<Netns>,
 }
 
 impl CtlIter {
     /// Return an iterator over the complete sysctl tree.
     pub fn root() -> Self {
-        let entries: Vec<walkdir::DirEntry> = walkdir::WalkDir::new("/proc/sys")
-            .sort_by(|a, b| a.path().cmp(b.path()))
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .collect();
-        CtlIter {
-            direntries: entries,
-            base: "/proc/sys".to_owned(),
-            cur_idx: 0,
-        }
+        CtlIter::root_at(&default_root())
+    }
+
+    /// Return an iterator over the complete sysctl tree rooted at `root`
+    /// instead of `/proc/sys`.
+    pub fn root_at(root: &str) -> Self {
+        let root = root.trim_end_matches('/');
+        CtlIter::new(root, root.to_owned(), false, None)
+    }
+
+    /// Return an iterator over the complete sysctl tree as seen from the
+    /// network namespace `netns`.
+    pub fn root_in_netns(netns: &Netns) -> Self {
+        let root = default_root();
+        let root = root.trim_end_matches('/');
+        CtlIter::new(root, root.to_owned(), false, Some(netns.clone()))
     }
 
     /// Return an iterator over all sysctl entries below the given node.
+    ///
+    /// The returned entries infer the type of their values if `node` does,
+    /// and are bound to the same network namespace.
     pub fn below(node: Ctl) -> Self {
-        let root = node.path();
-        let entries: Vec<walkdir::DirEntry> = walkdir::WalkDir::new(&root)
+        CtlIter::new(
+            node.root(),
+            node.path(),
+            node.is_typed(),
+            node.netns().cloned(),
+        )
+    }
+
+    fn new(root: &str, base: String, typed: bool, netns: Option<Netns>) -> Self {
+        let walker = walkdir::WalkDir::new(&base)
             .sort_by(|a, b| a.path().cmp(b.path()))
             .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .collect();
+            .into_iter();
         CtlIter {
-            direntries: entries,
-            base: root,
-            cur_idx: 0,
+            walker: Some(walker),
+            root: root.to_owned(),
+            typed,
+            netns,
+        }
+    }
+
+    fn next_file(&mut self) -> Result<Option<walkdir::DirEntry>, SysctlError> {
+        fn next_file(walker: &mut walkdir::IntoIter) -> Option<walkdir::DirEntry> {
+            loop {
+                match walker.next()? {
+                    Ok(e) if e.file_type().is_file() => return Some(e),
+                    _ => continue,
+                }
+            }
         }
+
+        let mut walker = match self.walker.take() {
+            Some(w) => w,
+            None => return Ok(None),
+        };
+        // The directories below `net` have to be listed from within the
+        // network namespace.
+        let e = match &self.netns {
+            Some(netns) => {
+                let (w, e) = netns.run(move || {
+                    let e = next_file(&mut walker);
+                    (walker, e)
+                })?;
+                walker = w;
+                e
+            }
+            None => next_file(&mut walker),
+        };
+        self.walker = Some(walker);
+        Ok(e)
     }
 }
 
@@ -50,24 +100,47 @@ impl Iterator for CtlIter {
     type Item = Result<Ctl, SysctlError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur_idx >= self.direntries.len() {
-            return None;
-        }
+        let e = match self.next_file() {
+            Ok(e) => e?,
+            Err(e) => return Some(Err(e)),
+        };
 
-        let e: &walkdir::DirEntry = &self.direntries[self.cur_idx];
-        self.cur_idx += 1;
+        match e.path().to_str() {
+            Some(path) => Some(
+                Ctl::lookup(&self.root, path, self.netns.clone()).map(|c| c.with_typed(self.typed)),
+            ),
+            None => Some(Err(SysctlError::ParseError)),
+        }
+    }
+}
 
-        // We continue iterating as long as the oid starts with the base
-        if let Some(path) = e.path().to_str() {
-            if path.starts_with(&self.base) {
-                Some(Ctl::new(path))
-            } else {
-                None
-            }
-        } else {
-            Some(Err(SysctlError::ParseError))
+// Returns the names of the directories and sysctls in the directory `dir`
+// of the tree rooted at `root`, in the order `CtlIter` walks them, with
+// whether each is a directory.
+pub(crate) fn children(root: &str, dir: &CtlName) -> Result<Vec<(String, bool)>, SysctlError> {
+    let path = match dir.components().is_empty() {
+        true => root.to_owned(),
+        false => format!("{}/{}", root, dir.to_path()),
+    };
+    let mut children = vec![];
+    for e in std::fs::read_dir(&path).map_err(|e| read_error(e, &path))? {
+        let e = e?;
+        let file_type = e.file_type()?;
+        if !file_type.is_dir() && !file_type.is_file() {
+            continue;
         }
+        let file_name = e
+            .file_name()
+            .into_string()
+            .map_err(|_| SysctlError::ParseError)?;
+        let name = CtlName::from_components(dir.components().iter().cloned().chain([file_name]));
+        children.push((name, file_type.is_dir()));
     }
+    children.sort();
+    Ok(children
+        .into_iter()
+        .map(|(name, is_dir)| (name.to_string(), is_dir))
+        .collect())
 }
 
 /// Ctl implements the IntoIterator trait to allow for easy iteration
@@ -87,7 +160,7 @@ impl IntoIterator for Ctl {
     type Item = Result<Ctl, SysctlError>;
     type IntoIter = CtlIter;
 
-    fn into_iter(self: Self) -> Self::IntoIter {
+    fn into_iter(self) -> Self::IntoIter {
         CtlIter::below(self)
     }
 }
@@ -106,6 +179,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ctl_iter_root_at() {
+        let root = crate::sys::tests::fake_root(
+            "ctl_iter_root_at",
+            &[
+                ("kernel/ostype", "Fake\n"),
+                ("net/core/somaxconn", "4096\n"),
+                ("net/ipv4/ip_forward", "0\n"),
+            ],
+        );
+
+        let names: Vec<String> = super::CtlIter::root_at(&root)
+            .map(|c| c.expect("ctl").name().expect("name"))
+            .collect();
+        assert_eq!(
+            names,
+            ["kernel.ostype", "net.core.somaxconn", "net.ipv4.ip_forward"]
+        );
+
+        let net = super::Ctl::new_in(&root, "net").expect("Ctl::new_in");
+        let ctls: Vec<super::Ctl> = net.into_iter().map(|c| c.expect("ctl")).collect();
+        assert_eq!(ctls.len(), 2);
+        assert!(ctls.iter().all(|c| c.root() == root));
+    }
+
+    #[test]
+    fn ctl_iter_children() {
+        let root = crate::sys::tests::fake_root(
+            "ctl_iter_children",
+            &[
+                ("kernel/ostype", "Fake\n"),
+                ("net/ipv4/conf/eth0.100/rp_filter", "1\n"),
+                ("net/ipv4/conf/eth0/rp_filter", "1\n"),
+                ("net/ipv4/ip_forward", "0\n"),
+            ],
+        );
+        let children =
+            |path| super::children(&root, &crate::CtlName::from_path(path)).expect("children");
+        assert_eq!(
+            children(""),
+            [("kernel".to_owned(), true), ("net".to_owned(), true)]
+        );
+        assert_eq!(
+            children("net/ipv4"),
+            [
+                ("net.ipv4.conf".to_owned(), true),
+                ("net.ipv4.ip_forward".to_owned(), false)
+            ]
+        );
+        assert_eq!(
+            children("net/ipv4/conf"),
+            [
+                ("net.ipv4.conf.eth0".to_owned(), true),
+                ("net.ipv4.conf.eth0/100".to_owned(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn ctl_iter_lazy() {
+        let root = crate::sys::tests::fake_root(
+            "ctl_iter_lazy",
+            &[("a/first", "1\n"), ("b/second", "2\n")],
+        );
+
+        let mut iter = super::CtlIter::root_at(&root);
+        let first = iter.next().expect("first").expect("ctl");
+        assert_eq!(first.name().expect("name"), "a.first");
+
+        // Directories are only read once the walk reaches them.
+        std::fs::write(format!("{}/b/third", root), "3\n").expect("write");
+        let rest: Vec<String> = iter
+            .map(|c| c.expect("ctl").name().expect("name"))
+            .collect();
+        assert_eq!(rest, ["b.second", "b.third"]);
+    }
+
     #[test]
     fn ctl_iter_below_compare_outputs() {
         // NOTE: Some linux distributions require Root permissions
@@ -136,8 +286,10 @@ mod tests {
                     actual.push(format!(
                         "{} = {}",
                         name,
-                        ctl.value_string()
-                            .expect(&format!("could not get value as string for {}", name))
+                        ctl.value_string().unwrap_or_else(|_| panic!(
+                            "could not get value as string for {}",
+                            name
+                        ))
                     ));
                 }
                 _ => panic!("sysctl not string type"),
@@ -0,0 +1,124 @@
+// linux/ctl_name.rs
+
+/// The name of a Linux sysctl, split into its components.
+///
+/// Components may contain dots themselves, such as the VLAN interface
+/// `eth0.100` in `/proc/sys/net/ipv4/conf/eth0.100/rp_filter`. Like
+/// procps `sysctl`, such dots are written as slashes in the dotted form of
+/// the name: `net.ipv4.conf.eth0/100.rp_filter`.
+///
+/// # Example
+/// ```
+/// let name = sysctl::CtlName::from("net/ipv4/conf/eth0.100/rp_filter");
+/// assert_eq!(name.to_string(), "net.ipv4.conf.eth0/100.rp_filter");
+/// assert_eq!(name.components()[3], "eth0.100");
+/// assert_eq!(sysctl::CtlName::from("net.ipv4.conf.eth0/100.rp_filter"), name);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CtlName {
+    components: Vec<String>,
+}
+
+impl CtlName {
+    /// Construct a name from its components.
+    pub fn from_components<I, S>(components: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        CtlName {
+            components: components
+                .into_iter()
+                .map(Into::into)
+                .filter(|c: &String| !c.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Construct a name from a path relative to the sysctl root, such as
+    /// `net/ipv4/conf/eth0.100/rp_filter`. Dots are never treated as
+    /// separators.
+    pub fn from_path(path: &str) -> Self {
+        CtlName::from_components(path.split('/'))
+    }
+
+    /// Returns the components of the name.
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+
+    /// Returns the name as a path relative to the sysctl root.
+    pub fn to_path(&self) -> String {
+        self.components.join("/")
+    }
+}
+
+/// Parse a name the way procps `sysctl` does.
+///
+/// If the first separator in the name is a slash, the name is taken to be
+/// in path form. Otherwise dots separate the components and slashes stand
+/// for dots within a component.
+impl From<&str> for CtlName {
+    fn from(name: &str) -> Self {
+        match name.find(['.', '/']).map(|i| &name[i..i + 1]) {
+            Some("/") | None => CtlName::from_path(name),
+            Some(_) => CtlName::from_components(name.split('.').map(|c| c.replace('/', "."))),
+        }
+    }
+}
+
+impl std::fmt::Display for CtlName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let components: Vec<String> = self
+            .components
+            .iter()
+            .map(|c| c.replace('.', "/"))
+            .collect();
+        write!(f, "{}", components.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CtlName;
+
+    #[test]
+    fn ctl_name_dotted() {
+        let name = CtlName::from("net.ipv4.ip_forward");
+        assert_eq!(name.components(), ["net", "ipv4", "ip_forward"]);
+        assert_eq!(name.to_path(), "net/ipv4/ip_forward");
+        assert_eq!(name.to_string(), "net.ipv4.ip_forward");
+    }
+
+    #[test]
+    fn ctl_name_escaped() {
+        let name = CtlName::from("net.ipv4.conf.eth0/100.rp_filter");
+        assert_eq!(
+            name.components(),
+            ["net", "ipv4", "conf", "eth0.100", "rp_filter"]
+        );
+        assert_eq!(name.to_path(), "net/ipv4/conf/eth0.100/rp_filter");
+        assert_eq!(name.to_string(), "net.ipv4.conf.eth0/100.rp_filter");
+    }
+
+    #[test]
+    fn ctl_name_path() {
+        let name = CtlName::from("net/ipv4/conf/eth0.100/rp_filter");
+        assert_eq!(
+            name.components(),
+            ["net", "ipv4", "conf", "eth0.100", "rp_filter"]
+        );
+        assert_eq!(
+            CtlName::from_path("/net/ipv4/conf/eth0.100/rp_filter/"),
+            name
+        );
+        assert_eq!(CtlName::from(name.to_string().as_str()), name);
+    }
+
+    #[test]
+    fn ctl_name_single() {
+        assert_eq!(CtlName::from("kernel").components(), ["kernel"]);
+        assert_eq!(CtlName::from("").components(), [] as [&str; 0]);
+        assert_eq!(CtlName::from("kernel..ostype").to_path(), "kernel/ostype");
+    }
+}
@@ -0,0 +1,326 @@
+// linux/dump.rs
+
+use super::funcs::default_root;
+use crate::ctl_error::SysctlError;
+
+use std::ffi::{CStr, CString};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Reads the values of a complete sysctl tree at once, much faster than
+/// reading every `Ctl` of a `CtlIter`.
+///
+/// Directories are walked through their file descriptors with `openat`,
+/// so no path is built or resolved for each sysctl, and values are read
+/// into a buffer that is reused for every sysctl. The values can be read
+/// by several threads.
+///
+/// Sysctls that cannot be read are left out, like `sysctl -a` does.
+///
+/// # Example
+/// ```no_run
+/// let values = sysctl::Dump::root().threads(4).read().unwrap();
+/// for (name, value) in values {
+///     println!("{} = {}", name, value);
+/// }
+/// ```
+pub struct Dump {
+    root: String,
+    threads: usize,
+}
+
+// A run of files in one directory that are next to each other in the
+// sorted tree.
+struct Run {
+    // The directory, relative to the root.
+    dir: CString,
+    // The name of each sysctl and its file name in `dir`.
+    files: Vec<(String, CString)>,
+}
+
+impl Dump {
+    /// Return a dump of the complete sysctl tree.
+    pub fn root() -> Self {
+        Dump::root_at(&default_root())
+    }
+
+    /// Return a dump of the complete sysctl tree rooted at `root` instead
+    /// of `/proc/sys`.
+    pub fn root_at(root: &str) -> Self {
+        Dump {
+            root: root.trim_end_matches('/').to_owned(),
+            threads: 1,
+        }
+    }
+
+    /// Read the values with `threads` threads. The default is 1, reading
+    /// them in the calling thread.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Return the name and value of every readable sysctl, in the same
+    /// order as `CtlIter`. Values are trimmed like `Ctl::value_string`
+    /// returns them.
+    pub fn read(&self) -> Result<Vec<(String, String)>, SysctlError> {
+        let root = open_dir(libc::AT_FDCWD, &CString::new(self.root.as_str())?)
+            .map_err(|_| SysctlError::NotFound(self.root.clone()))?;
+        let mut runs = vec![];
+        walk(&root, &CString::default(), &mut String::new(), &mut runs)?;
+
+        if self.threads == 1 {
+            return Ok(read_runs(&root, &runs));
+        }
+        // Split the runs into one contiguous chunk per thread, with about
+        // the same number of files each.
+        let total: usize = runs.iter().map(|r| r.files.len()).sum();
+        let per_thread = total.div_ceil(self.threads).max(1);
+        let mut chunks: Vec<&[Run]> = vec![];
+        let (mut start, mut files) = (0, 0);
+        for (i, run) in runs.iter().enumerate() {
+            files += run.files.len();
+            if files >= per_thread {
+                chunks.push(&runs[start..=i]);
+                (start, files) = (i + 1, 0);
+            }
+        }
+        chunks.push(&runs[start..]);
+
+        let root = &root;
+        Ok(std::thread::scope(|s| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| s.spawn(move || read_runs(root, chunk)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("dump thread panicked"))
+                .collect()
+        }))
+    }
+}
+
+fn open_dir(dirfd: RawFd, path: &CStr) -> std::io::Result<OwnedFd> {
+    open_at(dirfd, path, libc::O_DIRECTORY)
+}
+
+fn open_at(dirfd: RawFd, path: &CStr, flags: libc::c_int) -> std::io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::openat(
+            dirfd,
+            path.as_ptr(),
+            libc::O_RDONLY | libc::O_CLOEXEC | flags,
+        )
+    };
+    match fd {
+        -1 => Err(std::io::Error::last_os_error()),
+        fd => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+    }
+}
+
+// Return the entries of the directory `dir` sorted by name, each with
+// whether it is a directory. Symbolic links are left out.
+fn read_dir(dir: &OwnedFd) -> std::io::Result<Vec<(CString, bool)>> {
+    // fdopendir takes ownership of the descriptor, so give it a copy.
+    let fd = unsafe { libc::dup(dir.as_raw_fd()) };
+    if fd == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stream = unsafe { libc::fdopendir(fd) };
+    if stream.is_null() {
+        let e = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(e);
+    }
+
+    let mut entries = vec![];
+    loop {
+        let entry = unsafe { libc::readdir(stream) };
+        if entry.is_null() {
+            break;
+        }
+        let (name, d_type) = unsafe { (CStr::from_ptr((*entry).d_name.as_ptr()), (*entry).d_type) };
+        if name.to_bytes() == b"." || name.to_bytes() == b".." {
+            continue;
+        }
+        let is_dir = match d_type {
+            libc::DT_DIR => true,
+            libc::DT_REG => false,
+            libc::DT_UNKNOWN => {
+                let mut st: libc::stat = unsafe { std::mem::zeroed() };
+                let ret = unsafe {
+                    libc::fstatat(
+                        dir.as_raw_fd(),
+                        name.as_ptr(),
+                        &mut st,
+                        libc::AT_SYMLINK_NOFOLLOW,
+                    )
+                };
+                match st.st_mode & libc::S_IFMT {
+                    _ if ret == -1 => continue,
+                    libc::S_IFDIR => true,
+                    libc::S_IFREG => false,
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        entries.push((name.to_owned(), is_dir));
+    }
+    unsafe { libc::closedir(stream) };
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+// Collect the files below `dir` into `runs`. `path` is the path of `dir`
+// relative to the root and `name` its sysctl name.
+fn walk(
+    dir: &OwnedFd,
+    path: &CStr,
+    name: &mut String,
+    runs: &mut Vec<Run>,
+) -> Result<(), SysctlError> {
+    let mut run = Run {
+        dir: path.to_owned(),
+        files: vec![],
+    };
+    for (entry, is_dir) in read_dir(dir)? {
+        let component = String::from_utf8_lossy(entry.to_bytes()).replace('.', "/");
+        let name_len = name.len();
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(&component);
+
+        if is_dir {
+            if !run.files.is_empty() {
+                let dir = run.dir.clone();
+                runs.push(std::mem::replace(&mut run, Run { dir, files: vec![] }));
+            }
+            // Directories that cannot be opened are left out.
+            if let Ok(subdir) = open_dir(dir.as_raw_fd(), &entry) {
+                let mut subpath = path.to_bytes().to_vec();
+                if !subpath.is_empty() {
+                    subpath.push(b'/');
+                }
+                subpath.extend_from_slice(entry.to_bytes());
+                let subpath = CString::new(subpath).expect("no nul in path");
+                walk(&subdir, &subpath, name, runs)?;
+            }
+        } else {
+            run.files.push((name.clone(), entry));
+        }
+        name.truncate(name_len);
+    }
+    if !run.files.is_empty() {
+        runs.push(run);
+    }
+    Ok(())
+}
+
+// Read the values of the files in `runs`, reusing a single buffer.
+fn read_runs(root: &OwnedFd, runs: &[Run]) -> Vec<(String, String)> {
+    let mut values = Vec::with_capacity(runs.iter().map(|r| r.files.len()).sum());
+    let mut buf = vec![0u8; 4096];
+    let dot = c".";
+    for run in runs {
+        let dir = match run.dir.is_empty() {
+            true => open_dir(root.as_raw_fd(), dot),
+            false => open_dir(root.as_raw_fd(), &run.dir),
+        };
+        let dir = match dir {
+            Ok(dir) => dir,
+            Err(_) => continue,
+        };
+        for (name, file) in &run.files {
+            if let Some(value) = read_file(&dir, file, &mut buf) {
+                values.push((name.clone(), value));
+            }
+        }
+    }
+    values
+}
+
+// Read the file `file` in `dir` into `buf`, growing it as needed, and
+// return the trimmed value.
+fn read_file(dir: &OwnedFd, file: &CStr, buf: &mut Vec<u8>) -> Option<String> {
+    let fd = open_at(dir.as_raw_fd(), file, 0).ok()?;
+    let mut len = 0;
+    loop {
+        if len == buf.len() {
+            buf.resize(buf.len() * 2, 0);
+        }
+        let n = unsafe {
+            libc::read(
+                fd.as_raw_fd(),
+                buf[len..].as_mut_ptr().cast(),
+                buf.len() - len,
+            )
+        };
+        match n {
+            -1 if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => {
+                continue;
+            }
+            -1 => return None,
+            0 => break,
+            n => len += n as usize,
+        }
+    }
+    let value = std::str::from_utf8(&buf[..len]).ok()?;
+    Some(value.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dump;
+    use crate::{CtlIter, Sysctl};
+
+    #[test]
+    fn dump_fake_root() {
+        let long = "x".repeat(10000);
+        let root = super::super::tests::fake_root(
+            "dump_fake_root",
+            &[
+                ("kernel/hostname", "fake\n"),
+                ("kernel/keys/maxkeys", "200\n"),
+                ("kernel/kptr_restrict", "1\n"),
+                ("kernel/ostype", "Linux\n"),
+                ("net/ipv4/conf/eth0.100/rp_filter", "2\n"),
+                ("net/ipv4/conf/eth0/rp_filter", "1\n"),
+                ("net/ipv4/tcp_rmem", "4096\t131072\t6291456\n"),
+                ("vm/long", &long),
+                ("vm/swappiness", "60\n"),
+            ],
+        );
+        let expected: Vec<(String, String)> = CtlIter::root_at(&root)
+            .map(|c| {
+                let c = c.expect("ctl");
+                (c.name().expect("name"), c.value_string().expect("value"))
+            })
+            .collect();
+        assert_eq!(expected[1].0, "kernel.keys.maxkeys");
+        assert_eq!(expected[4].0, "net.ipv4.conf.eth0.rp_filter");
+        assert_eq!(expected[5].0, "net.ipv4.conf.eth0/100.rp_filter");
+
+        for threads in [1, 2, 3, 16] {
+            let values = Dump::root_at(&root).threads(threads).read().expect("read");
+            assert_eq!(values, expected, "{} threads", threads);
+        }
+        assert!(Dump::root_at(&format!("{}/missing", root)).read().is_err());
+    }
+
+    #[test]
+    fn dump_proc_sys() {
+        // Values can change between reads, so only compare names. The
+        // dump leaves out what cannot be read.
+        let all: Vec<String> = CtlIter::root()
+            .map(|c| c.expect("ctl").name().expect("name"))
+            .collect();
+        let dumped = Dump::root().threads(4).read().expect("read");
+        assert!(dumped.iter().any(|(name, _)| name == "kernel.ostype"));
+        let mut all = all.iter();
+        for (name, _) in &dumped {
+            assert!(all.any(|n| n == name), "{} out of order", name);
+        }
+    }
+}
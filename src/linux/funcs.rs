@@ -1,57 +1,383 @@
 // linux/funcs.rs
 
+use crate::consts::*;
 use crate::ctl_error::*;
 use crate::ctl_value::*;
 
 use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
 
-pub fn path_to_name(name: &str) -> String {
-    name.to_owned()
-        .replace("/proc/sys/", "")
-        .replace("..", ".")
-        .replace("/", ".")
+/// The directory the sysctl tree is read from by default.
+pub const PROC_SYS: &str = "/proc/sys";
+
+/// Environment variable that overrides `PROC_SYS`.
+pub const ROOT_ENV: &str = "SYSCTL_ROOT";
+
+/// Returns the root of the sysctl tree, honouring `ROOT_ENV`.
+pub fn default_root() -> String {
+    match std::env::var(ROOT_ENV) {
+        Ok(root) if !root.is_empty() => root,
+        _ => PROC_SYS.to_owned(),
+    }
+}
+
+/// Returns true if the sysctl can be written but not read, such as
+/// `vm.drop_caches`.
+pub fn is_write_only(md: &std::fs::Metadata) -> bool {
+    let mode = md.permissions().mode();
+    md.is_file() && mode & 0o444 == 0 && mode & 0o222 != 0
+}
+
+/// Returns true if the calling process may access the sysctl with `mode`,
+/// which is `libc::R_OK` or `libc::W_OK`.
+///
+/// The check is done by the kernel with the effective user and group IDs
+/// and the capabilities of the process.
+pub fn access(name: &str, mode: libc::c_int) -> Result<bool, SysctlError> {
+    let c_name = std::ffi::CString::new(name)?;
+    let ret = unsafe { libc::faccessat(libc::AT_FDCWD, c_name.as_ptr(), mode, libc::AT_EACCESS) };
+    if ret == 0 {
+        return Ok(true);
+    }
+    let e = std::io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => Ok(false),
+        Some(libc::ENOENT) => Err(SysctlError::NotFound(name.into())),
+        _ => Err(e.into()),
+    }
+}
+
+/// Map the error of a read to a `SysctlError`.
+pub(crate) fn read_error(e: std::io::Error, name: &str) -> SysctlError {
+    match e.raw_os_error() {
+        Some(libc::ENOENT) => SysctlError::NotFound(name.into()),
+        Some(libc::EPERM) | Some(libc::EACCES) => {
+            if std::fs::metadata(name).is_ok_and(|md| is_write_only(&md)) {
+                SysctlError::NoReadAccess
+            } else {
+                SysctlError::PermissionDenied {
+                    name: name.into(),
+                    value: None,
+                }
+            }
+        }
+        _ => SysctlError::IoError(e),
+    }
+}
+
+/// Map the error of opening a sysctl to write `value` to a `SysctlError`.
+fn open_write_error(e: std::io::Error, name: &str, value: &str) -> SysctlError {
+    match e.raw_os_error() {
+        Some(libc::ENOENT) => SysctlError::NotFound(name.into()),
+        _ => write_error(e, name, value),
+    }
+}
+
+/// Map the error of a write of `value` to a `SysctlError`.
+///
+/// Some sysctls fail with ENOENT when the value names something that does
+/// not exist, such as an unknown algorithm written to
+/// `net.ipv4.tcp_congestion_control`, so it is an invalid value here.
+fn write_error(e: std::io::Error, name: &str, value: &str) -> SysctlError {
+    match e.raw_os_error() {
+        Some(libc::EINVAL) | Some(libc::ERANGE) | Some(libc::ENOENT) => SysctlError::InvalidValue {
+            name: name.into(),
+            value: value.into(),
+        },
+        Some(libc::EPERM) | Some(libc::EACCES) => SysctlError::PermissionDenied {
+            name: name.into(),
+            value: Some(value.into()),
+        },
+        Some(libc::EROFS) => SysctlError::ReadOnlyFilesystem {
+            name: name.into(),
+            value: value.into(),
+        },
+        _ => SysctlError::IoError(e),
+    }
+}
+
+/// Returns the `CTLFLAG_RD`, `CTLFLAG_WR` and `CTLFLAG_ANYBODY` flags that
+/// apply to the calling process.
+pub fn access_flags(name: &str) -> Result<libc::c_uint, SysctlError> {
+    let md = std::fs::metadata(name).map_err(SysctlError::IoError)?;
+    let mode = md.permissions().mode();
+    let mut flags = 0;
+    // Unlike other files, sysctls are not readable or writeable by
+    // root if the mode does not allow it, so check both.
+    if mode & 0o444 != 0 && access(name, libc::R_OK)? {
+        flags |= CTLFLAG_RD;
+    }
+    if mode & 0o222 != 0 && access(name, libc::W_OK)? {
+        flags |= CTLFLAG_WR;
+    }
+    if mode & 0o002 != 0 {
+        flags |= CTLFLAG_ANYBODY;
+    }
+    Ok(flags)
 }
 
 pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
-    let file_res = std::fs::OpenOptions::new()
+    let mut file = std::fs::OpenOptions::new()
         .read(true)
         .write(false)
-        .open(&name);
-
-    file_res
-        .map(|mut file| {
-            let mut v = String::new();
-            file.read_to_string(&mut v)?;
-            Ok(CtlValue::String(v.trim().to_owned()))
-        })
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                SysctlError::NotFound(name.into())
-            } else {
-                e.into()
-            }
-        })?
+        .open(name)
+        .map_err(|e| read_error(e, name))?;
+
+    let mut v = String::new();
+    file.read_to_string(&mut v)
+        .map_err(|e| read_error(e, name))?;
+    Ok(CtlValue::String(v.trim().to_owned()))
+}
+
+/// Read the value and convert it with `infer_value`.
+pub fn value_typed(name: &str) -> Result<CtlValue, SysctlError> {
+    match value(name)? {
+        CtlValue::String(s) => Ok(infer_value(&s)),
+        v => Ok(v),
+    }
+}
+
+/// Infer the type of a value read from `/proc/sys`.
+///
+/// A single integer is returned as the smallest of `Int`, `Uint`, `Long`
+/// and `Ulong` that can hold it. A value of several fields is returned as
+/// a `List` if its fields are separated by tabs, which is how the kernel
+/// prints vectors such as `net.ipv4.tcp_rmem`, or if they are all
+/// integers. Each field of a `List` is inferred like a single value, so
+/// fields that are not integers are kept as `String`. Anything else is
+/// returned as `String`.
+pub fn infer_value(s: &str) -> CtlValue {
+    let s = s.trim();
+    let fields: Vec<&str> = s.split_whitespace().collect();
+    if fields.len() > 1 {
+        let list: Vec<CtlValue> = fields
+            .iter()
+            .map(|f| infer_int(f).unwrap_or_else(|| CtlValue::String((*f).to_owned())))
+            .collect();
+        let all_ints = !list.iter().any(|v| matches!(v, CtlValue::String(_)));
+        if all_ints || s.contains('\t') {
+            return CtlValue::List(list);
+        }
+        return CtlValue::String(s.to_owned());
+    }
+    infer_int(s).unwrap_or_else(|| CtlValue::String(s.to_owned()))
+}
+
+fn infer_int(s: &str) -> Option<CtlValue> {
+    if let Ok(v) = s.parse::<i32>() {
+        Some(CtlValue::Int(v))
+    } else if let Ok(v) = s.parse::<u32>() {
+        Some(CtlValue::Uint(v))
+    } else if let Ok(v) = s.parse::<i64>() {
+        Some(CtlValue::Long(v))
+    } else if let Ok(v) = s.parse::<u64>() {
+        Some(CtlValue::Ulong(v))
+    } else {
+        None
+    }
 }
 
+/// Format a value the way it is written to `/proc/sys`.
+///
+/// The fields of a `List` are separated by tabs, which is also how the
+/// kernel prints them.
+fn value_to_string(v: CtlValue) -> Result<String, SysctlError> {
+    match v {
+        CtlValue::String(s) => Ok(s),
+        CtlValue::List(l) => {
+            let fields = l
+                .into_iter()
+                .map(|v| match v {
+                    CtlValue::List(_) => {
+                        Err(std::io::Error::from(std::io::ErrorKind::InvalidData).into())
+                    }
+                    v => value_to_string(v),
+                })
+                .collect::<Result<Vec<String>, SysctlError>>()?;
+            Ok(fields.join("\t"))
+        }
+        CtlValue::Int(_)
+        | CtlValue::Uint(_)
+        | CtlValue::Long(_)
+        | CtlValue::Ulong(_)
+        | CtlValue::U8(_)
+        | CtlValue::U16(_)
+        | CtlValue::U32(_)
+        | CtlValue::U64(_)
+        | CtlValue::S8(_)
+        | CtlValue::S16(_)
+        | CtlValue::S32(_)
+        | CtlValue::S64(_) => Ok(v.to_string()),
+        _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidData).into()),
+    }
+}
+
+/// Write the value and read it back.
+///
+/// Write-only sysctls cannot be read back, so `CtlValue::WriteOnly` is returned
+/// for them once the write has succeeded.
 pub fn set_value(name: &str, v: CtlValue) -> Result<CtlValue, SysctlError> {
-    let file_res = std::fs::OpenOptions::new()
+    let v = value_to_string(v)?;
+    let mut file = std::fs::OpenOptions::new()
         .read(false)
         .write(true)
-        .open(&name);
+        .open(name)
+        .map_err(|e| open_write_error(e, name, &v))?;
 
-    file_res
-        .map(|mut file| match v {
-            CtlValue::String(v) => {
-                file.write_all(&v.as_bytes())?;
-                value(&name)
-            }
-            _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidData).into()),
-        })
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                SysctlError::NotFound(name.into())
-            } else {
-                e.into()
-            }
-        })?
+    file.write_all(v.as_bytes())
+        .map_err(|e| write_error(e, name, &v))?;
+    if is_write_only(&file.metadata()?) {
+        return Ok(CtlValue::WriteOnly);
+    }
+    value(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CtlValue;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn infer_value() {
+        assert_eq!(super::infer_value("0"), CtlValue::Int(0));
+        assert_eq!(super::infer_value("-1\n"), CtlValue::Int(-1));
+        assert_eq!(super::infer_value("4294967295"), CtlValue::Uint(u32::MAX));
+        assert_eq!(
+            super::infer_value("-4294967296"),
+            CtlValue::Long(-(1 << 32))
+        );
+        assert_eq!(
+            super::infer_value("18446744073709551615"),
+            CtlValue::Ulong(u64::MAX)
+        );
+        assert_eq!(
+            super::infer_value("Linux"),
+            CtlValue::String("Linux".into())
+        );
+    }
+
+    #[test]
+    fn infer_value_list() {
+        assert_eq!(
+            super::infer_value("4096\t131072\t6291456\n"),
+            CtlValue::List(vec![
+                CtlValue::Int(4096),
+                CtlValue::Int(131072),
+                CtlValue::Int(6291456)
+            ])
+        );
+        assert_eq!(
+            super::infer_value("32768 60999"),
+            CtlValue::List(vec![CtlValue::Int(32768), CtlValue::Int(60999)])
+        );
+        assert_eq!(
+            super::infer_value("reno cubic"),
+            CtlValue::String("reno cubic".into())
+        );
+        assert_eq!(
+            super::infer_value("1\tcubic"),
+            CtlValue::List(vec![CtlValue::Int(1), CtlValue::String("cubic".into())])
+        );
+        assert_eq!(
+            super::infer_value("reno\tcubic\n"),
+            CtlValue::List(vec![
+                CtlValue::String("reno".into()),
+                CtlValue::String("cubic".into())
+            ])
+        );
+        assert_eq!(
+            super::infer_value("#1 SMP PREEMPT_DYNAMIC 2024"),
+            CtlValue::String("#1 SMP PREEMPT_DYNAMIC 2024".into())
+        );
+    }
+
+    #[test]
+    fn value_to_string_list() {
+        let v = CtlValue::List(vec![CtlValue::Int(4), CtlValue::Uint(4), CtlValue::Int(1)]);
+        assert_eq!(super::value_to_string(v).unwrap(), "4\t4\t1");
+
+        let v = CtlValue::List(vec![CtlValue::List(vec![])]);
+        assert!(super::value_to_string(v).is_err());
+    }
+
+    #[test]
+    fn set_value_write_only() {
+        let root = crate::sys::tests::fake_root("set_value_write_only", &[("vm/drop_caches", "")]);
+        let path = format!("{}/vm/drop_caches", root);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o200))
+            .expect("set_permissions");
+
+        let md = std::fs::metadata(&path).expect("metadata");
+        assert!(super::is_write_only(&md));
+        let v = super::set_value(&path, CtlValue::String("3".into())).expect("set_value");
+        assert_eq!(v, CtlValue::WriteOnly);
+    }
+
+    #[test]
+    fn access() {
+        assert!(super::access("/proc/sys/kernel/ostype", libc::R_OK).expect("access"));
+        assert!(!super::access("/proc/sys/kernel/ostype", libc::W_OK).expect("access"));
+        assert!(super::access("/proc/sys/does/not/exist", libc::R_OK).is_err());
+    }
+
+    #[test]
+    fn write_error() {
+        let e = std::io::Error::from_raw_os_error(libc::EINVAL);
+        assert!(matches!(
+            super::write_error(e, "vm.swappiness", "1000"),
+            crate::SysctlError::InvalidValue { name, value } if name == "vm.swappiness" && value == "1000"
+        ));
+        let e = std::io::Error::from_raw_os_error(libc::ENOENT);
+        assert!(matches!(
+            super::write_error(e, "net.ipv4.tcp_congestion_control", "nope"),
+            crate::SysctlError::InvalidValue { value, .. } if value == "nope"
+        ));
+        let e = std::io::Error::from_raw_os_error(libc::ENOENT);
+        assert!(matches!(
+            super::open_write_error(e, "vm.nope", "1"),
+            crate::SysctlError::NotFound(name) if name == "vm.nope"
+        ));
+        let e = std::io::Error::from_raw_os_error(libc::EPERM);
+        assert!(matches!(
+            super::open_write_error(e, "vm.swappiness", "10"),
+            crate::SysctlError::PermissionDenied { .. }
+        ));
+        let e = std::io::Error::from_raw_os_error(libc::EPERM);
+        assert!(matches!(
+            super::write_error(e, "vm.swappiness", "10"),
+            crate::SysctlError::PermissionDenied { value: Some(v), .. } if v == "10"
+        ));
+        let e = std::io::Error::from_raw_os_error(libc::EROFS);
+        assert!(matches!(
+            super::write_error(e, "vm.swappiness", "10"),
+            crate::SysctlError::ReadOnlyFilesystem { .. }
+        ));
+        let e = std::io::Error::from_raw_os_error(libc::EIO);
+        assert!(matches!(
+            super::write_error(e, "vm.swappiness", "10"),
+            crate::SysctlError::IoError(_)
+        ));
+    }
+
+    #[test]
+    fn set_value_permission_denied() {
+        // kernel.ostype is read-only, even for root.
+        let r = super::set_value("/proc/sys/kernel/ostype", CtlValue::String("Fake".into()));
+        assert!(matches!(
+            r,
+            Err(crate::SysctlError::PermissionDenied { value: Some(v), .. }) if v == "Fake"
+        ));
+    }
+
+    #[test]
+    fn value_typed() {
+        let v = super::value_typed("/proc/sys/kernel/pid_max").expect("value_typed");
+        assert!(v.as_int().is_some());
+
+        let v = super::value_typed("/proc/sys/kernel/ostype").expect("value_typed");
+        assert_eq!(v, CtlValue::String("Linux".into()));
+
+        let v = super::value_typed("/proc/sys/kernel/printk").expect("value_typed");
+        assert_eq!(v.as_list().map(|l| l.len()), Some(4));
+    }
 }
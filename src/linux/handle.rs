@@ -0,0 +1,258 @@
+// linux/handle.rs
+
+use super::ctl::Ctl;
+use super::funcs::read_error;
+use crate::ctl_error::SysctlError;
+use crate::traits::Sysctl;
+
+use std::os::unix::fs::FileExt;
+use std::str::FromStr;
+
+/// An open sysctl, for reading its value many times cheaply.
+///
+/// The file is opened once and every read is a single `pread` at offset 0
+/// into a buffer supplied by the caller, so reading and parsing the value
+/// does not allocate. A handle of a Ctl bound to a network namespace keeps
+/// reading in that namespace.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let ctl = sysctl::Ctl::new("fs.file-nr").unwrap();
+/// let handle = sysctl::CtlHandle::open(&ctl).unwrap();
+/// let mut buf = [0u8; 64];
+/// let mut fields = [0u64; 3];
+/// handle.read_fields(&mut buf, &mut fields).unwrap();
+/// println!("{} of {} file handles allocated", fields[0], fields[2]);
+/// ```
+#[derive(Debug)]
+pub struct CtlHandle {
+    file: std::fs::File,
+    ctl: Ctl,
+    path: String,
+    name: String,
+}
+
+impl CtlHandle {
+    /// Open the file of `ctl` for reading.
+    pub fn open(ctl: &Ctl) -> Result<Self, SysctlError> {
+        let path = ctl.path();
+        let open_path = path.clone();
+        let file = ctl
+            .run(move || std::fs::File::open(&open_path).map_err(|e| read_error(e, &open_path)))
+            .map_err(|e| ctl.named(e))?;
+        Ok(CtlHandle {
+            file,
+            ctl: ctl.clone(),
+            path,
+            name: ctl.name()?,
+        })
+    }
+
+    /// Returns the name of the sysctl.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the value into `buf` and return it with surrounding
+    /// whitespace removed. Fails with `SysctlError::BufferTooSmall` if the
+    /// value does not fit.
+    pub fn read<'a>(&self, buf: &'a mut [u8]) -> Result<&'a str, SysctlError> {
+        let n = loop {
+            match self.file.read_at(buf, 0) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(self.ctl.named(read_error(e, &self.path))),
+            }
+        };
+        // A full buffer may hold only the start of the value.
+        if n == buf.len() {
+            return Err(SysctlError::BufferTooSmall {
+                name: self.name.clone(),
+                size: buf.len(),
+            });
+        }
+        Ok(std::str::from_utf8(&buf[..n])?.trim())
+    }
+
+    /// Read a value holding a single field and parse it as `T`.
+    pub fn read_parsed<T: FromStr>(&self, buf: &mut [u8]) -> Result<T, SysctlError> {
+        self.read(buf)?.parse().map_err(|_| SysctlError::ParseError)
+    }
+
+    /// Read a value of whitespace-separated fields, such as `fs.file-nr`,
+    /// and parse them into `fields`. Returns the number of fields read,
+    /// which is at most the length of `fields`.
+    pub fn read_fields<T: FromStr>(
+        &self,
+        buf: &mut [u8],
+        fields: &mut [T],
+    ) -> Result<usize, SysctlError> {
+        let mut n = 0;
+        for (field, s) in fields.iter_mut().zip(self.read(buf)?.split_whitespace()) {
+            *field = s.parse().map_err(|_| SysctlError::ParseError)?;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// Reads a fixed set of sysctls through open handles, sharing one buffer.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let ctls = ["fs.file-nr", "net.netfilter.nf_conntrack_count"]
+///     .map(|name| sysctl::Ctl::new(name).unwrap());
+/// let mut sampler = sysctl::Sampler::new(ctls).unwrap();
+/// loop {
+///     sampler
+///         .sample(|name, value| println!("{} = {}", name, value))
+///         .unwrap();
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Sampler {
+    handles: Vec<CtlHandle>,
+    buf: Vec<u8>,
+}
+
+impl Sampler {
+    /// Open handles for `ctls`.
+    pub fn new<I: IntoIterator<Item = Ctl>>(ctls: I) -> Result<Self, SysctlError> {
+        let handles = ctls
+            .into_iter()
+            .map(|ctl| CtlHandle::open(&ctl))
+            .collect::<Result<_, _>>()?;
+        Ok(Sampler {
+            handles,
+            buf: vec![0; 4096],
+        })
+    }
+
+    /// Set the size of the buffer values are read into. The default is
+    /// 4096 bytes.
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buf = vec![0; size];
+        self
+    }
+
+    /// Returns the handles, in the order of the Ctls they were opened for.
+    pub fn handles(&self) -> &[CtlHandle] {
+        &self.handles
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Read every sysctl and pass its name and trimmed value to `f`, in
+    /// order. Stops at the first sysctl that cannot be read.
+    pub fn sample<F: FnMut(&str, &str)>(&mut self, mut f: F) -> Result<(), SysctlError> {
+        for handle in &self.handles {
+            f(&handle.name, handle.read(&mut self.buf)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CtlHandle, Sampler};
+    use crate::{Ctl, SysctlError};
+
+    #[test]
+    fn handle_read() {
+        let root = super::super::tests::fake_root(
+            "handle_read",
+            &[
+                ("fs/file-nr", "1184\t0\t9223372036854775807\n"),
+                ("kernel/ostype", "Linux\n"),
+                ("net/netfilter/nf_conntrack_count", "17\n"),
+            ],
+        );
+        let ctl = Ctl::new_in(&root, "fs.file-nr").expect("Ctl::new_in");
+        let handle = CtlHandle::open(&ctl).expect("open");
+        assert_eq!(handle.name(), "fs.file-nr");
+
+        let mut buf = [0u8; 64];
+        assert_eq!(
+            handle.read(&mut buf).expect("read"),
+            "1184\t0\t9223372036854775807"
+        );
+        let mut fields = [0u64; 4];
+        assert_eq!(
+            handle
+                .read_fields(&mut buf, &mut fields)
+                .expect("read_fields"),
+            3
+        );
+        assert_eq!(fields, [1184, 0, 9223372036854775807, 0]);
+        let mut fields = [0u64; 2];
+        assert_eq!(
+            handle
+                .read_fields(&mut buf, &mut fields)
+                .expect("read_fields"),
+            2
+        );
+        assert_eq!(fields, [1184, 0]);
+
+        // The handle sees the new value without reopening the file.
+        std::fs::write(
+            format!("{}/fs/file-nr", root),
+            "1200\t0\t9223372036854775807\n",
+        )
+        .expect("write");
+        assert_eq!(handle.read_parsed::<u32>(&mut buf).ok(), None);
+        assert_eq!(
+            handle
+                .read_fields(&mut buf, &mut fields)
+                .expect("read_fields"),
+            2
+        );
+        assert_eq!(fields, [1200, 0]);
+
+        let mut small = [0u8; 8];
+        assert!(matches!(
+            handle.read(&mut small),
+            Err(SysctlError::BufferTooSmall { size: 8, .. })
+        ));
+
+        let ostype = CtlHandle::open(&Ctl::new_in(&root, "kernel.ostype").expect("Ctl::new_in"))
+            .expect("open");
+        assert!(matches!(
+            ostype.read_parsed::<u32>(&mut buf),
+            Err(SysctlError::ParseError)
+        ));
+
+        let count = Ctl::new_in(&root, "net.netfilter.nf_conntrack_count").expect("Ctl::new_in");
+        let mut sampler = Sampler::new([ctl, count]).expect("Sampler::new");
+        assert_eq!(sampler.len(), 2);
+        let mut samples = vec![];
+        sampler
+            .sample(|name, value| samples.push(format!("{}={}", name, value)))
+            .expect("sample");
+        assert_eq!(
+            samples,
+            [
+                "fs.file-nr=1200\t0\t9223372036854775807",
+                "net.netfilter.nf_conntrack_count=17"
+            ]
+        );
+    }
+
+    #[test]
+    fn handle_proc_sys() {
+        let ctl = Ctl::new_in("/proc/sys", "kernel.ostype").expect("Ctl::new_in");
+        let handle = CtlHandle::open(&ctl).expect("open");
+        let mut buf = [0u8; 64];
+        for _ in 0..3 {
+            assert_eq!(handle.read(&mut buf).expect("read"), "Linux");
+        }
+    }
+}
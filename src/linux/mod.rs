@@ -2,4 +2,23 @@
 
 pub mod ctl;
 pub mod ctl_iter;
+pub mod ctl_name;
+pub mod dump;
 pub mod funcs;
+pub mod handle;
+pub mod netns;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    /// Create a fake sysctl tree in a temporary directory and return its path.
+    pub fn fake_root(name: &str, entries: &[(&str, &str)]) -> String {
+        let root = std::env::temp_dir().join(format!("sysctl-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, value) in entries {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).expect("create_dir_all");
+            std::fs::write(&path, value).expect("write");
+        }
+        root.to_str().unwrap().to_owned()
+    }
+}
@@ -0,0 +1,197 @@
+// linux/netns.rs
+
+use crate::ctl_error::SysctlError;
+
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::fs::MetadataExt;
+use std::sync::{Arc, Mutex, mpsc};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A network namespace to read and write sysctls in.
+///
+/// The values below `net` depend on the network namespace of the thread
+/// accessing them. A `Netns` owns a helper thread that has joined the
+/// namespace with `setns(2)` and accesses the sysctls on behalf of the
+/// `Ctl`s and `CtlIter`s bound to it, so the rest of the process stays in
+/// its own namespace. Joining a namespace requires `CAP_SYS_ADMIN`.
+///
+/// The helper thread exits once the `Netns` and all handles bound to it
+/// are dropped.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let netns = sysctl::Netns::from_name("blue").unwrap();
+/// let ctl = sysctl::Ctl::new_in_netns(&netns, "net.ipv4.ip_forward").unwrap();
+/// println!("ip_forward in blue: {}", ctl.value_string().unwrap());
+/// ```
+#[derive(Clone)]
+pub struct Netns {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    jobs: Mutex<mpsc::Sender<Job>>,
+    dev: u64,
+    ino: u64,
+}
+
+impl Netns {
+    /// Join the namespace named `name`, as created by `ip netns add`.
+    pub fn from_name(name: &str) -> Result<Self, SysctlError> {
+        Netns::from_path(format!("/var/run/netns/{}", name))
+    }
+
+    /// Join the namespace of the process `pid`.
+    pub fn from_pid(pid: libc::pid_t) -> Result<Self, SysctlError> {
+        Netns::from_path(format!("/proc/{}/ns/net", pid))
+    }
+
+    /// Join the namespace referred to by the file at `path`, such as
+    /// `/var/run/netns/<name>` or `/proc/<pid>/ns/net`.
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, SysctlError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SysctlError::NotFound(path.display().to_string()),
+            _ => SysctlError::IoError(e),
+        })?;
+        Netns::from_fd(file.into())
+    }
+
+    /// Join the namespace referred to by `fd`.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, SysctlError> {
+        let md = std::fs::File::from(fd.try_clone()?).metadata()?;
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("sysctl-netns".to_owned())
+            .spawn(move || {
+                let ret = unsafe { libc::setns(fd.as_raw_fd(), libc::CLONE_NEWNET) };
+                drop(fd);
+                if ret < 0 {
+                    let _ = ready_tx.send(Err(std::io::Error::last_os_error()));
+                    return;
+                }
+                let _ = ready_tx.send(Ok(()));
+                for job in rx {
+                    job();
+                }
+            })?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))??;
+
+        Ok(Netns {
+            inner: Arc::new(Inner {
+                jobs: Mutex::new(jobs),
+                dev: md.dev(),
+                ino: md.ino(),
+            }),
+        })
+    }
+
+    /// Run `f` on the thread that has joined the namespace and return its
+    /// result.
+    pub fn run<F, R>(&self, f: F) -> Result<R, SysctlError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move || {
+            let _ = tx.send(f());
+        });
+        let sent = match self.inner.jobs.lock() {
+            Ok(jobs) => jobs.send(job).is_ok(),
+            Err(_) => false,
+        };
+        match sent {
+            true => rx
+                .recv()
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe).into()),
+            false => Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe).into()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Netns {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Netns")
+            .field("dev", &self.inner.dev)
+            .field("ino", &self.inner.ino)
+            .finish()
+    }
+}
+
+/// Two `Netns` are equal if they refer to the same namespace.
+impl PartialEq for Netns {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.dev == other.inner.dev && self.inner.ino == other.inner.ino
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sysctl;
+
+    /// Create a new network namespace and return a handle to it, or None
+    /// if the process lacks the privileges to do so.
+    fn new_netns() -> Option<super::Netns> {
+        let fd = std::thread::spawn(|| {
+            if unsafe { libc::unshare(libc::CLONE_NEWNET) } < 0 {
+                return None;
+            }
+            std::fs::File::open("/proc/thread-self/ns/net").ok()
+        })
+        .join()
+        .unwrap()?;
+        super::Netns::from_fd(fd.into()).ok()
+    }
+
+    #[test]
+    fn netns_from_pid() {
+        let own = match super::Netns::from_pid(std::process::id() as libc::pid_t) {
+            Ok(ns) => ns,
+            Err(_) => return,
+        };
+        assert_eq!(own, own.clone());
+        let tid = own.run(|| unsafe { libc::gettid() }).expect("run");
+        assert_ne!(tid, unsafe { libc::gettid() });
+    }
+
+    #[test]
+    fn netns_ctl() {
+        let netns = match new_netns() {
+            Some(ns) => ns,
+            None => return,
+        };
+        let host = crate::Ctl::new("net.ipv4.ip_default_ttl").expect("Ctl::new");
+        let host_ttl = host.value_string().expect("value_string");
+
+        let ctl =
+            crate::Ctl::new_in_netns(&netns, "net.ipv4.ip_default_ttl").expect("Ctl::new_in_netns");
+        assert_eq!(ctl.netns(), Some(&netns));
+        let ttl = if host_ttl == "42" { "43" } else { "42" };
+        assert_eq!(ctl.set_value_string(ttl).expect("set_value_string"), ttl);
+        assert_eq!(host.value_string().expect("value_string"), host_ttl);
+
+        // A new namespace only has the loopback interface.
+        let conf = crate::Ctl::new_in_netns(&netns, "net.ipv4.conf").expect("Ctl::new_in_netns");
+        let ifaces: Vec<String> = conf
+            .into_iter()
+            .map(|c| c.expect("ctl").name().expect("name"))
+            .filter(|n| n.ends_with(".rp_filter"))
+            .collect();
+        assert_eq!(
+            ifaces,
+            [
+                "net.ipv4.conf.all.rp_filter",
+                "net.ipv4.conf.default.rp_filter",
+                "net.ipv4.conf.lo.rp_filter"
+            ]
+        );
+    }
+}
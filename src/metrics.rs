@@ -0,0 +1,547 @@
+// metrics.rs
+
+use crate::backend::{Backend, SystemBackend};
+use crate::ctl_error::SysctlError;
+use crate::ctl_value::CtlValue;
+use crate::sysctl_conf::glob_match;
+use crate::traits::Sysctl;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Names for the fields of well-known sysctls holding several values.
+/// Fields of other sysctls are labelled with their index.
+const FIELD_NAMES: &[(&str, &[&str])] = &[
+    (
+        "fs.dentry-state",
+        &[
+            "nr_dentry",
+            "nr_unused",
+            "age_limit",
+            "want_pages",
+            "nr_negative",
+            "dummy",
+        ],
+    ),
+    ("fs.file-nr", &["allocated", "unused", "max"]),
+    ("fs.inode-nr", &["nr_inodes", "nr_free_inodes"]),
+    (
+        "fs.inode-state",
+        &[
+            "nr_inodes",
+            "nr_free_inodes",
+            "preshrink",
+            "dummy1",
+            "dummy2",
+            "dummy3",
+            "dummy4",
+        ],
+    ),
+    (
+        "kernel.printk",
+        &[
+            "console_loglevel",
+            "default_message_loglevel",
+            "minimum_console_loglevel",
+            "default_console_loglevel",
+        ],
+    ),
+    ("kernel.sem", &["semmsl", "semmns", "semopm", "semmni"]),
+    ("net.ipv4.ip_local_port_range", &["min", "max"]),
+    ("net.ipv4.tcp_mem", &["low", "pressure", "high"]),
+    ("net.ipv4.tcp_rmem", &["min", "default", "max"]),
+    ("net.ipv4.tcp_wmem", &["min", "default", "max"]),
+    ("net.ipv4.udp_mem", &["low", "pressure", "high"]),
+    (
+        "vm.lowmem_reserve_ratio",
+        &["dma", "dma32", "normal", "movable"],
+    ),
+];
+
+/// Subtrees holding one directory of sysctls per network interface. Their
+/// sysctls are exported as one metric per setting, labelled with
+/// `interface`.
+const INTERFACE_SUBTREES: &[&str] = &[
+    "net.ipv4.conf",
+    "net.ipv4.neigh",
+    "net.ipv6.conf",
+    "net.ipv6.neigh",
+];
+
+/// Whether a metric only ever goes up, or can go up and down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl std::fmt::Display for MetricType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MetricType::Counter => write!(f, "counter"),
+            MetricType::Gauge => write!(f, "gauge"),
+        }
+    }
+}
+
+/// Renders numeric sysctls in the Prometheus text exposition format.
+///
+/// Each sysctl becomes a metric named after it, with the prefix, an
+/// underscore and the sysctl name with every character that is not
+/// allowed in a metric name replaced by an underscore, so
+/// `fs.file-nr` becomes `sysctl_fs_file_nr`. Sysctls holding several
+/// numbers get one sample per number, labelled with `field`. The
+/// per-interface sysctls below `net.ipv4.conf`, `net.ipv4.neigh`,
+/// `net.ipv6.conf` and `net.ipv6.neigh` are labelled with `interface`
+/// instead of having it in the metric name, so
+/// `net.ipv4.conf.eth0.forwarding` is a sample of
+/// `sysctl_net_ipv4_conf_forwarding`.
+///
+/// Metrics are gauges unless they match a `counter` pattern. Counters get
+/// a `_total` suffix. Sysctls whose value is not numeric or cannot be read
+/// are left out, as are subtrees that do not exist. Sysctls in several of
+/// the subtrees are only exported once. A sysctl whose metric name is
+/// already used by a different sysctl, such as `fs.file_nr` after
+/// `fs.file-nr`, is left out as well.
+///
+/// Patterns are shell globs matched against the whole sysctl name.
+///
+/// # Example
+/// ```no_run
+/// let metrics = sysctl::Exporter::new()
+///     .subtree("fs")
+///     .subtree("net.netfilter")
+///     .allow("fs.file-nr")
+///     .allow("net.netfilter.nf_conntrack_*")
+///     .deny("net.netfilter.nf_conntrack_helper")
+///     .render()
+///     .unwrap();
+/// print!("{}", metrics);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    prefix: String,
+    subtrees: Vec<String>,
+    allow: Vec<String>,
+    deny: Vec<String>,
+    counters: Vec<String>,
+}
+
+impl Default for Exporter {
+    fn default() -> Self {
+        Exporter {
+            prefix: "sysctl".to_owned(),
+            subtrees: vec![],
+            allow: vec![],
+            deny: vec![],
+            counters: vec![],
+        }
+    }
+}
+
+impl Exporter {
+    /// Return an exporter for the complete sysctl tree with the `sysctl`
+    /// prefix.
+    pub fn new() -> Self {
+        Exporter::default()
+    }
+
+    /// Set the prefix of the metric names. An empty prefix leaves the
+    /// names unprefixed.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Export the sysctls below `name` instead of the complete tree. Can
+    /// be called several times. Subtrees that do not exist are skipped.
+    pub fn subtree(mut self, name: &str) -> Self {
+        self.subtrees.push(name.to_owned());
+        self
+    }
+
+    /// Only export sysctls matching one of the allow patterns. Without
+    /// any, all sysctls are allowed.
+    pub fn allow(mut self, pattern: &str) -> Self {
+        self.allow.push(pattern.to_owned());
+        self
+    }
+
+    /// Never export sysctls matching `pattern`, even if they are allowed.
+    pub fn deny(mut self, pattern: &str) -> Self {
+        self.deny.push(pattern.to_owned());
+        self
+    }
+
+    /// Export sysctls matching `pattern` as counters instead of gauges.
+    pub fn counter(mut self, pattern: &str) -> Self {
+        self.counters.push(pattern.to_owned());
+        self
+    }
+
+    /// Return whether the sysctl `name` passes the allow and deny lists.
+    pub fn is_exported(&self, name: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|p| glob_match(p, name)))
+            && !self.deny.iter().any(|p| glob_match(p, name))
+    }
+
+    /// Return the type of the metric for the sysctl `name`.
+    pub fn metric_type(&self, name: &str) -> MetricType {
+        match self.counters.iter().any(|p| glob_match(p, name)) {
+            true => MetricType::Counter,
+            false => MetricType::Gauge,
+        }
+    }
+
+    /// Return the metric name for the sysctl `name`. Per-interface sysctls
+    /// share the name of their setting.
+    pub fn metric_name(&self, name: &str) -> String {
+        let family = match split_interface(name) {
+            Some((prefix, _, rest)) => format!("{}.{}", prefix, rest),
+            None => name.to_owned(),
+        };
+        let mut metric = match self.prefix.is_empty() {
+            true => String::new(),
+            false => format!("{}_", self.prefix),
+        };
+        metric.extend(family.chars().map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':' => c,
+            _ => '_',
+        }));
+        if metric.starts_with(|c: char| c.is_ascii_digit()) {
+            metric.insert(0, '_');
+        }
+        if self.metric_type(name) == MetricType::Counter && !metric.ends_with("_total") {
+            metric.push_str("_total");
+        }
+        metric
+    }
+
+    /// Render the system sysctls.
+    pub fn render(&self) -> Result<String, SysctlError> {
+        self.render_from(&SystemBackend)
+    }
+
+    /// Render the sysctls of `backend`.
+    pub fn render_from<B: Backend>(&self, backend: &B) -> Result<String, SysctlError> {
+        let mut families = Families::default();
+        if self.subtrees.is_empty() {
+            self.collect(&mut families, backend.iter())?;
+        }
+        for subtree in &self.subtrees {
+            let node = match backend.ctl(subtree) {
+                Ok(node) => node,
+                Err(SysctlError::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            self.collect(&mut families, backend.iter_below(node))?;
+        }
+
+        let mut out = String::new();
+        for family in &families.list {
+            // Writing to a String cannot fail.
+            let _ = writeln!(
+                out,
+                "# HELP {} sysctl {}",
+                family.metric,
+                escape_help(&family.help)
+            );
+            let _ = writeln!(out, "# TYPE {} {}", family.metric, family.metric_type);
+            out.push_str(&family.samples);
+        }
+        Ok(out)
+    }
+
+    fn collect<C, I>(&self, families: &mut Families, iter: I) -> Result<(), SysctlError>
+    where
+        C: Sysctl,
+        I: Iterator<Item = Result<C, SysctlError>>,
+    {
+        for ctl in iter {
+            let ctl = ctl?;
+            let name = ctl.name()?;
+            if families.seen.contains(&name) || !self.is_exported(&name) {
+                continue;
+            }
+            let samples = match ctl.value().ok().as_ref().and_then(numbers) {
+                Some(samples) => samples,
+                None => continue,
+            };
+            if self.add_samples(families, &name, &samples) {
+                families.seen.insert(name);
+            }
+        }
+        Ok(())
+    }
+
+    // Add the samples of the sysctl `name` to its metric. Returns false if
+    // the metric belongs to a different sysctl.
+    fn add_samples(&self, families: &mut Families, name: &str, samples: &[String]) -> bool {
+        let metric = self.metric_name(name);
+        let (help, interface) = match split_interface(name) {
+            Some((prefix, interface, rest)) => (
+                format!("{}.<interface>.{}", prefix, rest),
+                Some(interface.replace('/', ".")),
+            ),
+            None => (name.to_owned(), None),
+        };
+        let i = match families.index.get(&metric) {
+            Some(&i) if families.list[i].help == help => i,
+            Some(_) => return false,
+            None => {
+                let i = families.list.len();
+                families.index.insert(metric.clone(), i);
+                families.list.push(Family {
+                    metric: metric.clone(),
+                    help,
+                    metric_type: self.metric_type(name),
+                    samples: String::new(),
+                });
+                i
+            }
+        };
+        let out = &mut families.list[i].samples;
+
+        let fields = FIELD_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, fields)| *fields)
+            .unwrap_or(&[]);
+        for (i, sample) in samples.iter().enumerate() {
+            let mut labels = vec![];
+            if let Some(interface) = &interface {
+                labels.push(format!("interface=\"{}\"", escape_label(interface)));
+            }
+            if samples.len() > 1 {
+                match fields.get(i) {
+                    Some(field) => labels.push(format!("field=\"{}\"", field)),
+                    None => labels.push(format!("field=\"{}\"", i)),
+                }
+            }
+            let _ = match labels.is_empty() {
+                true => writeln!(out, "{} {}", metric, sample),
+                false => writeln!(out, "{}{{{}}} {}", metric, labels.join(","), sample),
+            };
+        }
+        true
+    }
+}
+
+// The metrics rendered so far, in the order they were first seen.
+#[derive(Default)]
+struct Families {
+    list: Vec<Family>,
+    // Index into `list` by metric name.
+    index: HashMap<String, usize>,
+    // The sysctls already rendered, so overlapping subtrees export them
+    // once.
+    seen: HashSet<String>,
+}
+
+struct Family {
+    metric: String,
+    // The sysctl, or the per-interface sysctls, the metric is for.
+    help: String,
+    metric_type: MetricType,
+    samples: String,
+}
+
+// Split a per-interface sysctl name into the subtree, the interface and
+// the setting, or return None for other sysctls.
+fn split_interface(name: &str) -> Option<(&str, &str, &str)> {
+    INTERFACE_SUBTREES.iter().find_map(|prefix| {
+        let (interface, rest) = name
+            .strip_prefix(prefix)?
+            .strip_prefix('.')?
+            .split_once('.')?;
+        Some((*prefix, interface, rest))
+    })
+}
+
+// Escape a label value as the text format requires.
+fn escape_label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Escape a HELP text as the text format requires.
+fn escape_help(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+// Return the numbers held by `value`, formatted as samples, or None if
+// the value is not numeric.
+fn numbers(value: &CtlValue) -> Option<Vec<String>> {
+    let number = match value {
+        CtlValue::Int(v) => v.to_string(),
+        CtlValue::S64(v) => v.to_string(),
+        CtlValue::Uint(v) => v.to_string(),
+        CtlValue::Long(v) => v.to_string(),
+        CtlValue::Ulong(v) => v.to_string(),
+        CtlValue::U64(v) => v.to_string(),
+        CtlValue::U8(v) => v.to_string(),
+        CtlValue::U16(v) => v.to_string(),
+        CtlValue::S8(v) => v.to_string(),
+        CtlValue::S16(v) => v.to_string(),
+        CtlValue::S32(v) => v.to_string(),
+        CtlValue::U32(v) => v.to_string(),
+        #[cfg(target_os = "freebsd")]
+        CtlValue::Temperature(t) => t.celsius().to_string(),
+        // The Linux backend returns all values as strings, with several
+        // fields separated by whitespace.
+        CtlValue::String(s) => {
+            let fields: Vec<String> = s.split_whitespace().map(str::to_owned).collect();
+            let numeric = fields.iter().all(|f| f.parse::<i128>().is_ok());
+            return (numeric && !fields.is_empty()).then_some(fields);
+        }
+        CtlValue::List(values) => {
+            let mut fields = vec![];
+            for value in values {
+                fields.extend(numbers(value)?);
+            }
+            return (!fields.is_empty()).then_some(fields);
+        }
+        _ => return None,
+    };
+    Some(vec![number])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exporter, MetricType};
+    use crate::{CtlValue, MockBackend};
+
+    fn mock() -> MockBackend {
+        let mock = MockBackend::new();
+        mock.insert(
+            "fs.file-nr",
+            CtlValue::String("1184\t0\t9223372036854775807".to_owned()),
+        );
+        mock.insert(
+            "fs.file-max",
+            CtlValue::String("9223372036854775807".to_owned()),
+        );
+        mock.insert("kernel.ostype", CtlValue::String("Linux".to_owned()));
+        mock.insert("kernel.pid_max", CtlValue::Int(4194304));
+        mock.insert("net.netfilter.nf_conntrack_count", CtlValue::Uint(17));
+        mock.insert("net.netfilter.nf_conntrack_helper", CtlValue::Int(0));
+        mock.insert(
+            "net.ipv4.tcp_rmem",
+            CtlValue::List(vec![
+                CtlValue::Int(4096),
+                CtlValue::Int(131072),
+                CtlValue::Int(6291456),
+            ]),
+        );
+        mock.insert("net.ipv4.conf.all.forwarding", CtlValue::Int(1));
+        mock.insert("net.ipv4.conf.eth0/100.forwarding", CtlValue::Int(0));
+        mock.insert("vm.stat.pgfault", CtlValue::U64(123456789));
+        mock.insert("vm.weird", CtlValue::String("1 2 3".to_owned()));
+        mock
+    }
+
+    #[test]
+    fn metrics_names() {
+        let exporter = Exporter::new().counter("vm.stat.*");
+        assert_eq!(exporter.metric_name("fs.file-nr"), "sysctl_fs_file_nr");
+        assert_eq!(
+            exporter.metric_name("net.ipv4.conf.eth0/100.forwarding"),
+            "sysctl_net_ipv4_conf_forwarding"
+        );
+        assert_eq!(
+            exporter.metric_name("net.ipv4.ip_forward"),
+            "sysctl_net_ipv4_ip_forward"
+        );
+        assert_eq!(
+            exporter.metric_name("vm.stat.pgfault"),
+            "sysctl_vm_stat_pgfault_total"
+        );
+        assert_eq!(exporter.metric_type("vm.stat.pgfault"), MetricType::Counter);
+        assert_eq!(exporter.metric_type("fs.file-nr"), MetricType::Gauge);
+
+        let exporter = Exporter::new().prefix("");
+        assert_eq!(exporter.metric_name("1.a"), "_1_a");
+    }
+
+    #[test]
+    fn metrics_render() {
+        let metrics = Exporter::new()
+            .subtree("fs")
+            .subtree("kernel")
+            .subtree("net")
+            .subtree("net.ipv4")
+            .subtree("does.not.exist")
+            .deny("fs.file-max")
+            .deny("*_helper")
+            .render_from(&mock())
+            .expect("render_from");
+        assert_eq!(
+            metrics,
+            "# HELP sysctl_fs_file_nr sysctl fs.file-nr\n\
+             # TYPE sysctl_fs_file_nr gauge\n\
+             sysctl_fs_file_nr{field=\"allocated\"} 1184\n\
+             sysctl_fs_file_nr{field=\"unused\"} 0\n\
+             sysctl_fs_file_nr{field=\"max\"} 9223372036854775807\n\
+             # HELP sysctl_kernel_pid_max sysctl kernel.pid_max\n\
+             # TYPE sysctl_kernel_pid_max gauge\n\
+             sysctl_kernel_pid_max 4194304\n\
+             # HELP sysctl_net_ipv4_conf_forwarding sysctl net.ipv4.conf.<interface>.forwarding\n\
+             # TYPE sysctl_net_ipv4_conf_forwarding gauge\n\
+             sysctl_net_ipv4_conf_forwarding{interface=\"all\"} 1\n\
+             sysctl_net_ipv4_conf_forwarding{interface=\"eth0.100\"} 0\n\
+             # HELP sysctl_net_ipv4_tcp_rmem sysctl net.ipv4.tcp_rmem\n\
+             # TYPE sysctl_net_ipv4_tcp_rmem gauge\n\
+             sysctl_net_ipv4_tcp_rmem{field=\"min\"} 4096\n\
+             sysctl_net_ipv4_tcp_rmem{field=\"default\"} 131072\n\
+             sysctl_net_ipv4_tcp_rmem{field=\"max\"} 6291456\n\
+             # HELP sysctl_net_netfilter_nf_conntrack_count sysctl net.netfilter.nf_conntrack_count\n\
+             # TYPE sysctl_net_netfilter_nf_conntrack_count gauge\n\
+             sysctl_net_netfilter_nf_conntrack_count 17\n"
+        );
+    }
+
+    #[test]
+    fn metrics_render_all() {
+        let metrics = Exporter::new()
+            .prefix("node_sysctl")
+            .allow("vm.*")
+            .counter("vm.stat.*")
+            .render_from(&mock())
+            .expect("render_from");
+        assert_eq!(
+            metrics,
+            "# HELP node_sysctl_vm_stat_pgfault_total sysctl vm.stat.pgfault\n\
+             # TYPE node_sysctl_vm_stat_pgfault_total counter\n\
+             node_sysctl_vm_stat_pgfault_total 123456789\n\
+             # HELP node_sysctl_vm_weird sysctl vm.weird\n\
+             # TYPE node_sysctl_vm_weird gauge\n\
+             node_sysctl_vm_weird{field=\"0\"} 1\n\
+             node_sysctl_vm_weird{field=\"1\"} 2\n\
+             node_sysctl_vm_weird{field=\"2\"} 3\n"
+        );
+    }
+
+    #[test]
+    fn metrics_collisions() {
+        let mock = MockBackend::new();
+        mock.insert("fs.file-nr", CtlValue::Int(1));
+        mock.insert("fs.file_nr", CtlValue::Int(2));
+        mock.insert("net.ipv4.conf.eth\"0\\.forwarding", CtlValue::Int(1));
+        mock.insert("net.ipv4.conf.forwarding", CtlValue::Int(0));
+        mock.insert("vm.a\\b", CtlValue::Int(3));
+        let metrics = Exporter::new().render_from(&mock).expect("render_from");
+        assert_eq!(
+            metrics,
+            "# HELP sysctl_fs_file_nr sysctl fs.file-nr\n\
+             # TYPE sysctl_fs_file_nr gauge\n\
+             sysctl_fs_file_nr 1\n\
+             # HELP sysctl_net_ipv4_conf_forwarding sysctl net.ipv4.conf.<interface>.forwarding\n\
+             # TYPE sysctl_net_ipv4_conf_forwarding gauge\n\
+             sysctl_net_ipv4_conf_forwarding{interface=\"eth\\\"0\\\\\"} 1\n\
+             # HELP sysctl_vm_a_b sysctl vm.a\\\\b\n\
+             # TYPE sysctl_vm_a_b gauge\n\
+             sysctl_vm_a_b 3\n"
+        );
+    }
+}
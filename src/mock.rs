@@ -0,0 +1,561 @@
+// mock.rs
+
+use crate::backend::Backend;
+use crate::ctl_error::SysctlError;
+use crate::ctl_flags::CtlFlags;
+use crate::ctl_info::CtlInfo;
+use crate::ctl_type::CtlType;
+use crate::ctl_value::CtlValue;
+use crate::traits::Sysctl;
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A sysctl stored in a `MockBackend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockEntry {
+    value: CtlValue,
+    ctl_type: CtlType,
+    flags: u32,
+    fmt: String,
+    description: String,
+}
+
+impl MockEntry {
+    /// A readable and writable sysctl holding `value`, whose type is the
+    /// type of `value`.
+    pub fn new(value: CtlValue) -> Self {
+        MockEntry {
+            ctl_type: CtlType::from(&value),
+            value,
+            flags: CtlFlags::RW.bits(),
+            fmt: String::new(),
+            description: String::new(),
+        }
+    }
+
+    /// Set the flags. Reads fail without `CtlFlags::RD` and writes fail
+    /// without `CtlFlags::WR`.
+    pub fn with_flags(mut self, flags: CtlFlags) -> Self {
+        self.flags = flags.bits();
+        self
+    }
+
+    /// Set the type reported by `value_type` and `info`.
+    ///
+    /// Values written to a sysctl of type `CtlType::String` are converted
+    /// to strings. Other types only accept values of the same type.
+    pub fn with_type(mut self, ctl_type: CtlType) -> Self {
+        self.ctl_type = ctl_type;
+        self
+    }
+
+    /// Set the format string reported by `info`.
+    pub fn with_fmt(mut self, fmt: &str) -> Self {
+        self.fmt = fmt.to_owned();
+        self
+    }
+
+    /// Set the description.
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_owned();
+        self
+    }
+}
+
+impl From<CtlValue> for MockEntry {
+    fn from(value: CtlValue) -> Self {
+        MockEntry::new(value)
+    }
+}
+
+/// An in-memory sysctl tree for testing code that uses sysctls.
+///
+/// Names are dotted and the nodes are implied by the names of the entries
+/// below them. Clones of a `MockBackend` share the same tree, so values
+/// written through a `MockCtl` can be checked with `MockBackend::get`.
+///
+/// # Example
+/// ```
+/// # use sysctl::{Backend, CtlFlags, CtlValue, MockEntry, Sysctl};
+/// let mock = sysctl::MockBackend::new();
+/// mock.insert("net.ipv4.ip_forward", CtlValue::Int(0));
+/// mock.insert(
+///     "kernel.ostype",
+///     MockEntry::new(CtlValue::String("Linux".to_owned())).with_flags(CtlFlags::RD),
+/// );
+///
+/// let ctl = mock.ctl("net.ipv4.ip_forward").unwrap();
+/// assert_eq!(ctl.set_value_string("1").unwrap(), "1");
+/// assert_eq!(mock.get("net.ipv4.ip_forward"), Some(CtlValue::Int(1)));
+/// assert!(mock.ctl("kernel.ostype").unwrap().set_value_string("BSD").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockBackend {
+    entries: Arc<Mutex<BTreeMap<String, MockEntry>>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        MockBackend::default()
+    }
+
+    /// Add or replace the sysctl `name`.
+    pub fn insert<E: Into<MockEntry>>(&self, name: &str, entry: E) {
+        self.lock().insert(name.to_owned(), entry.into());
+    }
+
+    /// Remove the sysctl `name`.
+    pub fn remove(&self, name: &str) -> Option<MockEntry> {
+        self.lock().remove(name)
+    }
+
+    /// Returns the value of the sysctl `name`, regardless of its flags.
+    pub fn get(&self, name: &str) -> Option<CtlValue> {
+        self.lock().get(name).map(|e| e.value.clone())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, MockEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Returns the entry for `name`, None for a node, or NotFound.
+    fn entry(&self, name: &str) -> Result<Option<MockEntry>, SysctlError> {
+        let entries = self.lock();
+        if let Some(e) = entries.get(name) {
+            return Ok(Some(e.clone()));
+        }
+        let prefix = format!("{}.", name);
+        match entries.range(prefix.clone()..).next() {
+            Some((k, _)) if k.starts_with(&prefix) => Ok(None),
+            _ => Err(SysctlError::NotFound(name.to_owned())),
+        }
+    }
+}
+
+impl Backend for MockBackend {
+    type Ctl = MockCtl;
+    type Iter = MockIter;
+
+    fn ctl(&self, name: &str) -> Result<Self::Ctl, SysctlError> {
+        self.entry(name)?;
+        Ok(MockCtl {
+            backend: self.clone(),
+            name: name.to_owned(),
+        })
+    }
+
+    fn iter(&self) -> Self::Iter {
+        MockIter {
+            backend: self.clone(),
+            base: String::new(),
+            last: None,
+        }
+    }
+
+    fn iter_below(&self, node: Self::Ctl) -> Self::Iter {
+        MockIter {
+            backend: node.backend,
+            base: node.name,
+            last: None,
+        }
+    }
+
+    /// Skips over the entries below each child node.
+    fn children(&self, node: Option<&str>) -> Result<Vec<(String, bool)>, SysctlError> {
+        let prefix = match node {
+            Some(name) => {
+                self.entry(name)?;
+                format!("{}.", name)
+            }
+            None => String::new(),
+        };
+        let entries = self.lock();
+        let mut children = vec![];
+        let mut from = Bound::Included(prefix.clone());
+        while let Some((name, _)) = entries.range::<String, _>((from, Bound::Unbounded)).next() {
+            let rest = match name.strip_prefix(&prefix) {
+                Some(rest) => rest,
+                None => break,
+            };
+            match rest.split_once('.') {
+                // '/' follows '.', so the next child node starts after
+                // all names below this one.
+                Some((child, _)) => {
+                    let child = format!("{}{}", prefix, child);
+                    from = Bound::Included(format!("{}/", child));
+                    match children.last_mut() {
+                        Some((last, is_node)) if *last == child => *is_node = true,
+                        _ => children.push((child, true)),
+                    }
+                }
+                None => {
+                    from = Bound::Excluded(name.clone());
+                    children.push((name.clone(), false));
+                }
+            }
+        }
+        Ok(children)
+    }
+}
+
+/// A sysctl in a `MockBackend`.
+#[derive(Debug, Clone)]
+pub struct MockCtl {
+    backend: MockBackend,
+    name: String,
+}
+
+impl MockCtl {
+    fn entry(&self) -> Result<Option<MockEntry>, SysctlError> {
+        self.backend.entry(&self.name)
+    }
+}
+
+impl Sysctl for MockCtl {
+    /// A `MockCtl` can only be obtained from `MockBackend::ctl`, so this
+    /// returns `SysctlError::NotSupported`.
+    fn new(_name: &str) -> Result<Self, SysctlError> {
+        Err(SysctlError::NotSupported)
+    }
+
+    /// Returns `SysctlError::NotSupported`, see `MockCtl::new`.
+    fn new_with_type(_name: &str, _ctl_type: CtlType, _fmt: &str) -> Result<Self, SysctlError> {
+        Err(SysctlError::NotSupported)
+    }
+
+    fn name(&self) -> Result<String, SysctlError> {
+        Ok(self.name.clone())
+    }
+
+    fn value_type(&self) -> Result<CtlType, SysctlError> {
+        Ok(self.info()?.ctl_type)
+    }
+
+    fn description(&self) -> Result<String, SysctlError> {
+        match self.entry()? {
+            Some(e) if !e.description.is_empty() => Ok(e.description),
+            _ => Ok("[N/A]".to_owned()),
+        }
+    }
+
+    fn value(&self) -> Result<CtlValue, SysctlError> {
+        match self.entry()? {
+            None => Ok(CtlValue::Node(vec![])),
+            Some(e) if e.flags & CtlFlags::RD.bits() == 0 => Err(SysctlError::NoReadAccess),
+            Some(e) => Ok(e.value),
+        }
+    }
+
+    fn value_string(&self) -> Result<String, SysctlError> {
+        self.value().map(|v| v.to_string())
+    }
+
+    fn value_as<T>(&self) -> Result<Box<T>, SysctlError> {
+        let bytes = match self.value()? {
+            CtlValue::Struct(b) | CtlValue::Node(b) => b,
+            _ => return Err(SysctlError::ExtractionError),
+        };
+        if bytes.len() != std::mem::size_of::<T>() {
+            return Err(SysctlError::ShortRead {
+                read: bytes.len(),
+                reported: std::mem::size_of::<T>(),
+            });
+        }
+        Ok(Box::new(unsafe {
+            std::ptr::read_unaligned(bytes.as_ptr() as *const T)
+        }))
+    }
+
+    /// Returns `CtlValue::WriteOnly` for write-only sysctls, like the Linux
+    /// backend does.
+    fn set_value(&self, value: CtlValue) -> Result<CtlValue, SysctlError> {
+        if self.entry()?.is_none() {
+            return Err(SysctlError::NoWriteAccess);
+        }
+        let mut entries = self.backend.lock();
+        let entry = entries
+            .get_mut(&self.name)
+            .ok_or_else(|| SysctlError::NotFound(self.name.clone()))?;
+        if entry.flags & CtlFlags::WR.bits() == 0 {
+            return Err(SysctlError::PermissionDenied {
+                name: self.name.clone(),
+                value: Some(value.to_string()),
+            });
+        }
+        let value = match CtlType::from(&value) {
+            t if t == entry.ctl_type => value,
+            _ if entry.ctl_type == CtlType::String => CtlValue::String(value.to_string()),
+            _ => {
+                return Err(SysctlError::InvalidValue {
+                    name: self.name.clone(),
+                    value: value.to_string(),
+                });
+            }
+        };
+        entry.value = value;
+        match entry.flags & CtlFlags::RD.bits() {
+            0 => Ok(CtlValue::WriteOnly),
+            _ => Ok(entry.value.clone()),
+        }
+    }
+
+    /// The string is parsed according to the type of the sysctl.
+    fn set_value_string(&self, value: &str) -> Result<String, SysctlError> {
+        let current = match self.entry()? {
+            Some(e) => e,
+            None => return Err(SysctlError::NoWriteAccess),
+        };
+        let parsed = parse_value(current.ctl_type, &current.value, value).ok_or_else(|| {
+            SysctlError::InvalidValue {
+                name: self.name.clone(),
+                value: value.to_owned(),
+            }
+        })?;
+        self.set_value(parsed).map(|v| v.to_string())
+    }
+
+    fn flags(&self) -> Result<CtlFlags, SysctlError> {
+        Ok(self.info()?.flags())
+    }
+
+    fn info(&self) -> Result<CtlInfo, SysctlError> {
+        Ok(match self.entry()? {
+            Some(e) => CtlInfo {
+                ctl_type: e.ctl_type,
+                fmt: e.fmt,
+                flags: e.flags,
+            },
+            None => CtlInfo {
+                ctl_type: CtlType::Node,
+                fmt: "N".to_owned(),
+                flags: CtlFlags::RD.bits(),
+            },
+        })
+    }
+}
+
+// Parse `s` as a value of type `ctl_type`. The fields of lists take the
+// types of the fields of the `current` value.
+fn parse_value(ctl_type: CtlType, current: &CtlValue, s: &str) -> Option<CtlValue> {
+    let s = s.trim();
+    Some(match ctl_type {
+        CtlType::String => CtlValue::String(s.to_owned()),
+        CtlType::Int => CtlValue::Int(s.parse().ok()?),
+        CtlType::Uint => CtlValue::Uint(s.parse().ok()?),
+        CtlType::Long => CtlValue::Long(s.parse().ok()?),
+        CtlType::Ulong => CtlValue::Ulong(s.parse().ok()?),
+        CtlType::S8 => CtlValue::S8(s.parse().ok()?),
+        CtlType::S16 => CtlValue::S16(s.parse().ok()?),
+        CtlType::S32 => CtlValue::S32(s.parse().ok()?),
+        CtlType::S64 => CtlValue::S64(s.parse().ok()?),
+        CtlType::U8 => CtlValue::U8(s.parse().ok()?),
+        CtlType::U16 => CtlValue::U16(s.parse().ok()?),
+        CtlType::U32 => CtlValue::U32(s.parse().ok()?),
+        CtlType::U64 => CtlValue::U64(s.parse().ok()?),
+        CtlType::List => {
+            let fields = match current {
+                CtlValue::List(fields) => fields.as_slice(),
+                _ => &[],
+            };
+            let values = s
+                .split_whitespace()
+                .enumerate()
+                .map(|(i, f)| match fields.get(i).or(fields.last()) {
+                    Some(field) => parse_value(CtlType::from(field), field, f),
+                    None => Some(CtlValue::String(f.to_owned())),
+                })
+                .collect::<Option<Vec<_>>>()?;
+            CtlValue::List(values)
+        }
+        _ => return None,
+    })
+}
+
+/// An iterator over the sysctls in a `MockBackend`.
+///
+/// The iterator does not hold a lock on the backend, and sees entries that
+/// are added behind its current position.
+#[derive(Debug, Clone)]
+pub struct MockIter {
+    backend: MockBackend,
+    base: String,
+    last: Option<String>,
+}
+
+impl Iterator for MockIter {
+    type Item = Result<MockCtl, SysctlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entries = self.backend.lock();
+        let prefix = match self.base.is_empty() {
+            true => String::new(),
+            false => format!("{}.", self.base),
+        };
+        let next = match &self.last {
+            None if entries.contains_key(&self.base) => Some(self.base.clone()),
+            Some(last) if *last != self.base => entries
+                .range::<String, _>((Bound::Excluded(last), Bound::Unbounded))
+                .next()
+                .map(|(k, _)| k.clone()),
+            _ => entries
+                .range(prefix.clone()..)
+                .next()
+                .map(|(k, _)| k.clone()),
+        };
+        drop(entries);
+
+        let name = next.filter(|k| *k == self.base || k.starts_with(&prefix))?;
+        self.last = Some(name.clone());
+        Some(Ok(MockCtl {
+            backend: self.backend.clone(),
+            name,
+        }))
+    }
+}
+
+impl IntoIterator for MockCtl {
+    type Item = Result<MockCtl, SysctlError>;
+    type IntoIter = MockIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let backend = self.backend.clone();
+        backend.iter_below(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockBackend, MockEntry};
+    use crate::{Backend, CtlFlags, CtlType, CtlValue, Sysctl, SysctlError};
+
+    fn mock() -> MockBackend {
+        let mock = MockBackend::new();
+        mock.insert("kernel.ostype", CtlValue::String("Linux".to_owned()));
+        mock.insert(
+            "kernel.osrelease",
+            MockEntry::new(CtlValue::String("6.1".to_owned())).with_flags(CtlFlags::RD),
+        );
+        mock.insert("kernel-x", CtlValue::Int(0));
+        mock.insert("kernel.pid_max", CtlValue::Int(32768));
+        mock.insert(
+            "net.ipv4.tcp_rmem",
+            CtlValue::List(vec![
+                CtlValue::Int(4096),
+                CtlValue::Int(131072),
+                CtlValue::Int(6291456),
+            ]),
+        );
+        mock.insert(
+            "vm.drop_caches",
+            MockEntry::new(CtlValue::Int(0)).with_flags(CtlFlags::WR),
+        );
+        mock
+    }
+
+    #[test]
+    fn mock_read() {
+        let mock = mock();
+        let ctl = mock.ctl("kernel.pid_max").expect("ctl");
+        assert_eq!(ctl.value().expect("value"), CtlValue::Int(32768));
+        assert_eq!(ctl.value_type().expect("value_type"), CtlType::Int);
+        assert_eq!(ctl.description().expect("description"), "[N/A]");
+
+        let node = mock.ctl("kernel").expect("ctl");
+        assert_eq!(node.value_type().expect("value_type"), CtlType::Node);
+        assert!(matches!(mock.ctl("kern"), Err(SysctlError::NotFound(_))));
+
+        let rmem = mock.ctl("net.ipv4.tcp_rmem").expect("ctl");
+        assert_eq!(
+            rmem.value_string().expect("value_string"),
+            "4096\t131072\t6291456"
+        );
+    }
+
+    #[test]
+    fn mock_write() {
+        let mock = mock();
+        let ctl = mock.ctl("kernel.pid_max").expect("ctl");
+        assert_eq!(ctl.set_value_string("4194304").expect("set"), "4194304");
+        assert_eq!(mock.get("kernel.pid_max"), Some(CtlValue::Int(4194304)));
+        assert!(matches!(
+            ctl.set_value_string("many"),
+            Err(SysctlError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            ctl.set_value(CtlValue::String("1".to_owned())),
+            Err(SysctlError::InvalidValue { .. })
+        ));
+
+        let rmem = mock.ctl("net.ipv4.tcp_rmem").expect("ctl");
+        rmem.set_value_string("8192 262144 8388608").expect("set");
+        assert_eq!(
+            mock.get("net.ipv4.tcp_rmem"),
+            Some(CtlValue::List(vec![
+                CtlValue::Int(8192),
+                CtlValue::Int(262144),
+                CtlValue::Int(8388608)
+            ]))
+        );
+
+        let ostype = mock.ctl("kernel.ostype").expect("ctl");
+        assert_eq!(
+            ostype.set_value(CtlValue::Int(1)).expect("set"),
+            CtlValue::String("1".to_owned())
+        );
+    }
+
+    #[test]
+    fn mock_permissions() {
+        let mock = mock();
+        let release = mock.ctl("kernel.osrelease").expect("ctl");
+        assert!(matches!(
+            release.set_value_string("6.2"),
+            Err(SysctlError::PermissionDenied { name, value: Some(v) })
+                if name == "kernel.osrelease" && v == "6.2"
+        ));
+
+        let drop_caches = mock.ctl("vm.drop_caches").expect("ctl");
+        assert!(matches!(
+            drop_caches.value(),
+            Err(SysctlError::NoReadAccess)
+        ));
+        assert_eq!(
+            drop_caches.set_value_string("3").expect("set"),
+            "[Write-only]"
+        );
+        assert_eq!(mock.get("vm.drop_caches"), Some(CtlValue::Int(3)));
+        assert_eq!(
+            drop_caches.flags().expect("flags").bits(),
+            CtlFlags::WR.bits()
+        );
+    }
+
+    #[test]
+    fn mock_iter() {
+        let mock = mock();
+        let names = |iter: super::MockIter| -> Vec<String> {
+            iter.map(|c| c.expect("ctl").name().expect("name"))
+                .collect()
+        };
+        assert_eq!(
+            names(mock.iter()),
+            [
+                "kernel-x",
+                "kernel.osrelease",
+                "kernel.ostype",
+                "kernel.pid_max",
+                "net.ipv4.tcp_rmem",
+                "vm.drop_caches"
+            ]
+        );
+        let kernel = mock.ctl("kernel").expect("ctl");
+        assert_eq!(
+            names(kernel.into_iter()),
+            ["kernel.osrelease", "kernel.ostype", "kernel.pid_max"]
+        );
+        let leaf = mock.ctl("kernel.ostype").expect("ctl");
+        assert_eq!(names(mock.iter_below(leaf)), ["kernel.ostype"]);
+    }
+}
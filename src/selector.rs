@@ -0,0 +1,427 @@
+// selector.rs
+
+use crate::backend::{Backend, SystemBackend};
+use crate::ctl_error::SysctlError;
+use crate::sysctl_conf::{glob_match, normalize};
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Glob(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+/// Selects the sysctls whose names match a glob or a regular expression.
+///
+/// The walk starts at the subtree named by the literal start of the
+/// pattern, so `net.ipv4.conf.*.rp_filter` reads `net.ipv4.conf` and
+/// `^vm\.dirty_` reads `vm`, not the complete tree. Globs are matched one
+/// component at a time, and nodes whose names cannot lead to a match are
+/// not descended into: `*.hostname` only lists the top-level nodes.
+/// Only matching sysctls are looked up.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let selector = sysctl::Selector::glob("net.ipv4.conf.*.rp_filter");
+/// for ctl in selector.select().unwrap() {
+///     println!("{} = {}", ctl.name().unwrap(), ctl.value_string().unwrap());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pattern: Pattern,
+}
+
+impl Selector {
+    /// Select sysctls matching a glob. Each dot-separated component of
+    /// the glob is matched against one component of the name, like
+    /// `glob(3)` matches paths: `*` matches any sequence of characters
+    /// within a component, `?` matches a single character and `[...]`
+    /// matches a set of characters. Names in slash form are accepted as
+    /// well.
+    pub fn glob(pattern: &str) -> Self {
+        Selector {
+            pattern: Pattern::Glob(normalize(pattern)),
+        }
+    }
+
+    /// Select sysctls matching a regular expression anywhere in their
+    /// name. Anchor it with `^` to restrict the walk to a subtree.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, SysctlError> {
+        Ok(Selector {
+            pattern: Pattern::Regex(regex::Regex::new(pattern)?),
+        })
+    }
+
+    /// Return whether the sysctl `name` matches.
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.pattern {
+            Pattern::Glob(pattern) => {
+                name.split('.').count() == pattern.split('.').count()
+                    && components_match(pattern, name)
+            }
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+
+    // Return whether sysctls below the node `name` can match.
+    fn may_match_below(&self, name: &str) -> bool {
+        match &self.pattern {
+            Pattern::Glob(pattern) => {
+                name.split('.').count() < pattern.split('.').count()
+                    && components_match(pattern, name)
+            }
+            #[cfg(feature = "regex")]
+            Pattern::Regex(_) => true,
+        }
+    }
+
+    /// Return the deepest node all matching sysctls are below, or None if
+    /// they can be anywhere in the tree.
+    pub fn subtree(&self) -> Option<String> {
+        let prefix = match &self.pattern {
+            Pattern::Glob(pattern) => glob_prefix(pattern).to_owned(),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex_prefix(regex.as_str()),
+        };
+        prefix
+            .rsplit_once('.')
+            .map(|(node, _)| node.to_owned())
+            .filter(|node| !node.is_empty())
+    }
+
+    /// Return the matching system sysctls.
+    pub fn select(&self) -> Result<Vec<crate::Ctl>, SysctlError> {
+        self.select_from(&SystemBackend)
+    }
+
+    /// Return the matching sysctls of `backend`, walking it with
+    /// `Backend::children` from `subtree`.
+    pub fn select_from<B: Backend>(&self, backend: &B) -> Result<Vec<B::Ctl>, SysctlError> {
+        let node = self.subtree();
+        if let Some(node) = &node {
+            match backend.ctl(node) {
+                Ok(_) => (),
+                Err(SysctlError::NotFound(_)) => return Ok(vec![]),
+                Err(e) => return Err(e),
+            }
+        }
+        let mut selected = vec![];
+        self.walk(backend, node.as_deref(), &mut selected)?;
+        Ok(selected)
+    }
+
+    fn walk<B: Backend>(
+        &self,
+        backend: &B,
+        node: Option<&str>,
+        selected: &mut Vec<B::Ctl>,
+    ) -> Result<(), SysctlError> {
+        for (name, is_node) in backend.children(node)? {
+            if is_node {
+                if self.may_match_below(&name) {
+                    self.walk(backend, Some(&name), selected)?;
+                }
+            } else if self.matches(&name) {
+                selected.push(backend.ctl(&name)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Return whether each component of `name` matches the component of the
+// glob `pattern` at the same position.
+fn components_match(pattern: &str, name: &str) -> bool {
+    name.split('.')
+        .zip(pattern.split('.'))
+        .all(|(n, p)| glob_match(p, n))
+}
+
+// Return the start of `pattern` up to the first wildcard.
+fn glob_prefix(pattern: &str) -> &str {
+    match pattern.find(['*', '?', '[']) {
+        Some(i) => &pattern[..i],
+        None => pattern,
+    }
+}
+
+// Return the literal text every match of an anchored `pattern` starts
+// with. Unanchored patterns and alternations can match anywhere, so they
+// have no prefix.
+#[cfg(feature = "regex")]
+fn regex_prefix(pattern: &str) -> String {
+    let mut prefix = String::new();
+    let rest = match pattern.strip_prefix('^') {
+        Some(rest) if !pattern.contains('|') => rest,
+        _ => return prefix,
+    };
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            '\\' => match chars.next() {
+                Some(c) if !c.is_ascii_alphanumeric() => c,
+                _ => break,
+            },
+            '.' | '*' | '+' | '?' | '(' | ')' | '[' | '{' | '^' | '$' => break,
+            c => c,
+        };
+        // A character that may be repeated zero times is not part of
+        // every match.
+        if matches!(chars.peek(), Some('*' | '?' | '{')) {
+            break;
+        }
+        prefix.push(literal);
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Selector;
+    use crate::{Backend, CtlValue, MockBackend, MockCtl, MockIter, Sysctl, SysctlError};
+    use std::cell::Cell;
+
+    fn mock() -> MockBackend {
+        let mock = MockBackend::new();
+        for name in [
+            "kernel.hostname",
+            "net.ipv4.conf.all.rp_filter",
+            "net.ipv4.conf.all.forwarding",
+            "net.ipv4.conf.eth0.rp_filter",
+            "net.ipv4.conf.eth0.forwarding",
+            "net.ipv4.ip_forward",
+            "vm.dirty_bytes",
+            "vm.dirty_ratio",
+            "vm.swappiness",
+        ] {
+            mock.insert(name, CtlValue::Int(0));
+        }
+        mock
+    }
+
+    fn names<C: Sysctl>(ctls: Vec<C>) -> Vec<String> {
+        ctls.iter().map(|c| c.name().expect("name")).collect()
+    }
+
+    #[test]
+    fn selector_glob() {
+        let mock = mock();
+        let selector = Selector::glob("net.ipv4.conf.*.rp_filter");
+        assert_eq!(selector.subtree().as_deref(), Some("net.ipv4.conf"));
+        assert_eq!(
+            names(selector.select_from(&mock).expect("select_from")),
+            [
+                "net.ipv4.conf.all.rp_filter",
+                "net.ipv4.conf.eth0.rp_filter"
+            ]
+        );
+
+        let selector = Selector::glob("net/ipv4/ip_forward");
+        assert_eq!(selector.subtree().as_deref(), Some("net.ipv4"));
+        assert_eq!(
+            names(selector.select_from(&mock).expect("select_from")),
+            ["net.ipv4.ip_forward"]
+        );
+
+        assert_eq!(Selector::glob("*.hostname").subtree(), None);
+        assert_eq!(
+            names(
+                Selector::glob("*.hostname")
+                    .select_from(&mock)
+                    .expect("select_from")
+            ),
+            ["kernel.hostname"]
+        );
+        // `*` does not match the dots between components.
+        assert!(!Selector::glob("*.rp_filter").matches("net.ipv4.conf.all.rp_filter"));
+        assert!(
+            Selector::glob("*.rp_filter")
+                .select_from(&mock)
+                .expect("select_from")
+                .is_empty()
+        );
+        assert!(
+            Selector::glob("fs.*")
+                .select_from(&mock)
+                .expect("select_from")
+                .is_empty()
+        );
+    }
+
+    // Counts the nodes listed and the sysctls looked up.
+    struct Counting {
+        mock: MockBackend,
+        listed: Cell<Vec<String>>,
+        looked_up: Cell<usize>,
+    }
+
+    impl Backend for Counting {
+        type Ctl = MockCtl;
+        type Iter = MockIter;
+
+        fn ctl(&self, name: &str) -> Result<MockCtl, SysctlError> {
+            self.looked_up.set(self.looked_up.get() + 1);
+            self.mock.ctl(name)
+        }
+
+        fn iter(&self) -> MockIter {
+            self.mock.iter()
+        }
+
+        fn iter_below(&self, node: MockCtl) -> MockIter {
+            self.mock.iter_below(node)
+        }
+
+        fn children(&self, node: Option<&str>) -> Result<Vec<(String, bool)>, SysctlError> {
+            let mut listed = self.listed.take();
+            listed.push(node.unwrap_or_default().to_owned());
+            self.listed.set(listed);
+            self.mock.children(node)
+        }
+    }
+
+    #[test]
+    fn selector_prunes() {
+        let mock = mock();
+        for i in 0..100 {
+            mock.insert(
+                &format!("net.ipv4.conf.eth{}.rp_filter", i),
+                CtlValue::Int(1),
+            );
+            mock.insert(
+                &format!("net.ipv4.conf.eth{}.forwarding", i),
+                CtlValue::Int(0),
+            );
+            mock.insert(
+                &format!("net.ipv4.neigh.eth{}.gc_stale_time", i),
+                CtlValue::Int(60),
+            );
+        }
+        let counting = Counting {
+            mock,
+            listed: Cell::new(vec![]),
+            looked_up: Cell::new(0),
+        };
+
+        // Only the interfaces are listed, and only their rp_filter is
+        // looked up, besides the starting node.
+        let selected = Selector::glob("net.ipv4.conf.eth?.rp_filter")
+            .select_from(&counting)
+            .expect("select_from");
+        assert_eq!(selected.len(), 10);
+        let listed = counting.listed.take();
+        assert_eq!(listed.len(), 11);
+        assert_eq!(listed[0], "net.ipv4.conf");
+        assert_eq!(counting.looked_up.replace(0), 11);
+
+        // A leading wildcard lists the top-level nodes only.
+        let selected = Selector::glob("*.hostname")
+            .select_from(&counting)
+            .expect("select_from");
+        assert_eq!(names(selected), ["kernel.hostname"]);
+        assert_eq!(counting.listed.take(), ["", "kernel", "net", "vm"]);
+        assert_eq!(counting.looked_up.get(), 1);
+    }
+
+    #[test]
+    fn selector_children() {
+        let mock = mock();
+        mock.insert("kernel-x", CtlValue::Int(0));
+        mock.insert("net.ipv4.conf.eth0/100.rp_filter", CtlValue::Int(0));
+        let children = |node| mock.children(node).expect("children");
+        assert_eq!(
+            children(None),
+            [
+                ("kernel-x".to_owned(), false),
+                ("kernel".to_owned(), true),
+                ("net".to_owned(), true),
+                ("vm".to_owned(), true)
+            ]
+        );
+        assert_eq!(
+            children(Some("net.ipv4.conf")),
+            [
+                ("net.ipv4.conf.all".to_owned(), true),
+                ("net.ipv4.conf.eth0".to_owned(), true),
+                ("net.ipv4.conf.eth0/100".to_owned(), true)
+            ]
+        );
+
+        // The default implementation finds the same children by walking
+        // the subtree.
+        struct Walking(MockBackend);
+
+        impl Backend for Walking {
+            type Ctl = MockCtl;
+            type Iter = MockIter;
+
+            fn ctl(&self, name: &str) -> Result<MockCtl, SysctlError> {
+                self.0.ctl(name)
+            }
+
+            fn iter(&self) -> MockIter {
+                self.0.iter()
+            }
+
+            fn iter_below(&self, node: MockCtl) -> MockIter {
+                self.0.iter_below(node)
+            }
+        }
+
+        let walking = Walking(mock.clone());
+        for node in [None, Some("net.ipv4.conf"), Some("vm")] {
+            assert_eq!(walking.children(node).expect("children"), children(node));
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn selector_regex() {
+        let mock = mock();
+        let selector = Selector::regex(r"^vm\.dirty_").expect("regex");
+        assert_eq!(selector.subtree().as_deref(), Some("vm"));
+        assert_eq!(
+            names(selector.select_from(&mock).expect("select_from")),
+            ["vm.dirty_bytes", "vm.dirty_ratio"]
+        );
+
+        let selector = Selector::regex(r"^net\.ipv4\.conf\.[^.]+\.forwarding$").expect("regex");
+        assert_eq!(selector.subtree().as_deref(), Some("net.ipv4.conf"));
+        assert_eq!(
+            names(selector.select_from(&mock).expect("select_from")),
+            [
+                "net.ipv4.conf.all.forwarding",
+                "net.ipv4.conf.eth0.forwarding"
+            ]
+        );
+
+        for pattern in [r"forward", r"^vm|^kernel", r"^net?\.ipv4", r"^(net)\.ipv4"] {
+            let selector = Selector::regex(pattern).expect("regex");
+            assert_eq!(selector.subtree(), None, "{}", pattern);
+        }
+        assert_eq!(
+            names(
+                Selector::regex("forward")
+                    .expect("regex")
+                    .select_from(&mock)
+                    .expect("select_from")
+            )
+            .len(),
+            3
+        );
+        assert!(Selector::regex("(").is_err());
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn selector_linux() {
+        let selector = Selector::glob("kernel.os*");
+        let names = names(selector.select().expect("select"));
+        assert!(names.contains(&"kernel.ostype".to_owned()));
+        assert!(names.iter().all(|n| n.starts_with("kernel.os")));
+    }
+}
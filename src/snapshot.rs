@@ -0,0 +1,397 @@
+// snapshot.rs
+
+use crate::backend::{Backend, SystemBackend};
+use crate::ctl_error::SysctlError;
+use crate::ctl_flags::CtlFlags;
+use crate::ctl_type::CtlType;
+use crate::traits::Sysctl;
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const HEADER: &str = "# sysctl snapshot v1";
+
+/// A sysctl as recorded in a `Snapshot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    /// The name of the sysctl.
+    pub name: String,
+
+    /// The type reported by `Ctl::info`.
+    pub ctl_type: CtlType,
+
+    /// The flags reported by `Ctl::info`.
+    pub flags: u32,
+
+    /// Whether the sysctl can be written, as reported by
+    /// `Sysctl::is_writable`. Unlike `CtlFlags::WR` on Linux, this does not
+    /// depend on the process that captured the snapshot.
+    pub writable: bool,
+
+    /// The value as returned by `Ctl::value`, formatted as a string.
+    pub value: String,
+}
+
+impl SnapshotEntry {
+    /// Return the flags for this sysctl.
+    pub fn flags(&self) -> CtlFlags {
+        CtlFlags::from_bits_truncate(self.flags)
+    }
+}
+
+/// The names, values, types and flags of a set of sysctls at one point in
+/// time.
+///
+/// Sysctls whose value cannot be read are left out.
+///
+/// # Example
+/// ```no_run
+/// let before = sysctl::Snapshot::capture().unwrap();
+/// before.save("/tmp/before.snapshot").unwrap();
+/// // Install a package, change a tunable, ...
+/// let after = sysctl::Snapshot::capture().unwrap();
+/// let before = sysctl::Snapshot::load("/tmp/before.snapshot").unwrap();
+/// print!("{}", before.diff(&after).to_sysctl_d());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    entries: BTreeMap<String, SnapshotEntry>,
+}
+
+impl Snapshot {
+    /// Capture the complete sysctl tree.
+    pub fn capture() -> Result<Self, SysctlError> {
+        Snapshot::capture_from(&SystemBackend)
+    }
+
+    /// Capture all sysctls of `backend`.
+    pub fn capture_from<B: Backend>(backend: &B) -> Result<Self, SysctlError> {
+        Snapshot::from_ctls(backend.iter())
+    }
+
+    /// Capture the sysctls below `node`.
+    pub fn capture_below(node: crate::Ctl) -> Result<Self, SysctlError> {
+        Snapshot::from_ctls(crate::CtlIter::below(node))
+    }
+
+    /// Capture the sysctls returned by an iterator such as `CtlIter`.
+    ///
+    /// Errors returned by the iterator itself are passed on.
+    pub fn from_ctls<C, I>(ctls: I) -> Result<Self, SysctlError>
+    where
+        C: Sysctl,
+        I: IntoIterator<Item = Result<C, SysctlError>>,
+    {
+        let mut snapshot = Snapshot::default();
+        for ctl in ctls {
+            let ctl = ctl?;
+            let name = ctl.name()?;
+            let (info, value, writable) = match (ctl.info(), ctl.value(), ctl.is_writable()) {
+                (Ok(info), Ok(value), Ok(writable)) => (info, value, writable),
+                _ => continue,
+            };
+            if info.ctl_type == CtlType::Node {
+                continue;
+            }
+            snapshot.insert(SnapshotEntry {
+                name,
+                ctl_type: info.ctl_type,
+                flags: info.flags,
+                writable,
+                value: value.to_string(),
+            });
+        }
+        Ok(snapshot)
+    }
+
+    /// Add or replace an entry.
+    pub fn insert(&mut self, entry: SnapshotEntry) {
+        self.entries.insert(entry.name.clone(), entry);
+    }
+
+    /// Returns the entry for the sysctl `name`.
+    pub fn get(&self, name: &str) -> Option<&SnapshotEntry> {
+        self.entries.get(name)
+    }
+
+    /// Returns the entries, sorted by name.
+    pub fn entries(&self) -> impl Iterator<Item = &SnapshotEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compare this snapshot with a later one.
+    pub fn diff(&self, target: &Snapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+        for (name, old) in &self.entries {
+            match target.entries.get(name) {
+                None => diff.removed.push(old.clone()),
+                Some(new) if new.value != old.value || new.ctl_type != old.ctl_type => {
+                    diff.changed.push((old.clone(), new.clone()))
+                }
+                Some(_) => (),
+            }
+        }
+        for (name, new) in &target.entries {
+            if !self.entries.contains_key(name) {
+                diff.added.push(new.clone());
+            }
+        }
+        diff
+    }
+
+    /// Write the snapshot to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SysctlError> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Read a snapshot written by `Snapshot::save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SysctlError> {
+        let file = std::fs::File::open(path)?;
+        Snapshot::read_from(std::io::BufReader::new(file))
+    }
+
+    /// Write the snapshot, one entry per line.
+    ///
+    /// Each line holds the name, the numeric type, the flags in hex,
+    /// whether the sysctl is writable as `0` or `1` and the value,
+    /// separated by tabs. Tabs, newlines and backslashes in the value are
+    /// escaped.
+    pub fn write_to<W: Write>(&self, mut w: W) -> Result<(), SysctlError> {
+        writeln!(w, "{}", HEADER)?;
+        for e in self.entries.values() {
+            writeln!(
+                w,
+                "{}\t{}\t{:x}\t{}\t{}",
+                e.name,
+                e.ctl_type as u32,
+                e.flags,
+                e.writable as u8,
+                escape(&e.value)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Read a snapshot written by `Snapshot::write_to`.
+    pub fn read_from<R: BufRead>(r: R) -> Result<Self, SysctlError> {
+        let mut lines = r.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(SysctlError::ParseError);
+        }
+
+        let mut snapshot = Snapshot::default();
+        for line in lines {
+            let line = line?;
+            let mut fields = line.splitn(5, '\t');
+            let (name, ctl_type, flags, writable, value) = match (
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+            ) {
+                (Some(n), Some(t), Some(f), Some(w), Some(v)) => (n, t, f, w, v),
+                _ => return Err(SysctlError::ParseError),
+            };
+            let writable = match writable {
+                "0" => false,
+                "1" => true,
+                _ => return Err(SysctlError::ParseError),
+            };
+            let ctl_type = ctl_type
+                .parse()
+                .ok()
+                .and_then(CtlType::from_u32)
+                .ok_or(SysctlError::ParseError)?;
+            let flags = u32::from_str_radix(flags, 16).map_err(|_| SysctlError::ParseError)?;
+            snapshot.insert(SnapshotEntry {
+                name: name.to_owned(),
+                ctl_type,
+                flags,
+                writable,
+                value: unescape(value)?,
+            });
+        }
+        Ok(snapshot)
+    }
+}
+
+/// The differences between two snapshots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    /// Sysctls only present in the target snapshot.
+    pub added: Vec<SnapshotEntry>,
+
+    /// Sysctls only present in the original snapshot.
+    pub removed: Vec<SnapshotEntry>,
+
+    /// Sysctls whose value or type differ, as (original, target).
+    pub changed: Vec<(SnapshotEntry, SnapshotEntry)>,
+}
+
+impl SnapshotDiff {
+    /// Are the snapshots the same?
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Render the diff as a sysctl.d drop-in that sets the values of the
+    /// target snapshot.
+    ///
+    /// Sysctls that cannot be written by any process, or whose value spans
+    /// several lines, are listed as comments. So are removed sysctls, which a drop-in
+    /// cannot reproduce.
+    pub fn to_sysctl_d(&self) -> String {
+        let mut out = String::new();
+        let mut targets: Vec<&SnapshotEntry> = self
+            .added
+            .iter()
+            .chain(self.changed.iter().map(|(_, new)| new))
+            .collect();
+        targets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for e in targets {
+            if !e.writable {
+                out += &format!("# {} is read-only\n", e.name);
+            } else if e.value.contains('\n') {
+                out += &format!("# {} has a multi-line value\n", e.name);
+            } else {
+                out += &format!("{} = {}\n", e.name, e.value);
+            }
+        }
+        for e in &self.removed {
+            out += &format!("# {} was removed\n", e.name);
+        }
+        out
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> Result<String, SysctlError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            _ => return Err(SysctlError::ParseError),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Snapshot, SnapshotEntry};
+    use crate::{CtlFlags, CtlType};
+
+    fn entry(name: &str, value: &str, flags: CtlFlags) -> SnapshotEntry {
+        SnapshotEntry {
+            name: name.to_owned(),
+            ctl_type: CtlType::String,
+            flags: flags.bits(),
+            writable: flags.contains(CtlFlags::WR),
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn snapshot_save_load() {
+        let mut snapshot = Snapshot::default();
+        snapshot.insert(entry("kernel.printk", "4\t4\t1\t7", CtlFlags::RW));
+        snapshot.insert(entry("kernel.weird", "a\\b\nc", CtlFlags::RD));
+
+        let mut buf = vec![];
+        snapshot.write_to(&mut buf).expect("write_to");
+        let loaded = Snapshot::read_from(buf.as_slice()).expect("read_from");
+        assert_eq!(loaded, snapshot);
+
+        assert!(Snapshot::read_from("kernel.printk\t3\t0\t1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let mut before = Snapshot::default();
+        before.insert(entry("a.same", "1", CtlFlags::RW));
+        before.insert(entry("b.changed", "1", CtlFlags::RW));
+        before.insert(entry("c.removed", "1", CtlFlags::RW));
+        before.insert(entry("d.readonly", "1", CtlFlags::RD));
+        let mut after = before.clone();
+        after.insert(entry("b.changed", "2", CtlFlags::RW));
+        after.insert(entry("d.readonly", "2", CtlFlags::RD));
+        after.insert(entry("e.added", "3 4", CtlFlags::RW));
+        after.entries.remove("c.removed");
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.added, [entry("e.added", "3 4", CtlFlags::RW)]);
+        assert_eq!(diff.removed, [entry("c.removed", "1", CtlFlags::RW)]);
+        let changed: Vec<&str> = diff.changed.iter().map(|(o, _)| o.name.as_str()).collect();
+        assert_eq!(changed, ["b.changed", "d.readonly"]);
+        assert!(before.diff(&before).is_empty());
+
+        assert_eq!(
+            diff.to_sysctl_d(),
+            "b.changed = 2\n# d.readonly is read-only\ne.added = 3 4\n# c.removed was removed\n"
+        );
+
+        // A snapshot captured without write access still restores
+        // writable sysctls.
+        let mut unprivileged = after.clone();
+        unprivileged.insert(SnapshotEntry {
+            writable: true,
+            ..entry("b.changed", "2", CtlFlags::RD)
+        });
+        assert_eq!(
+            before.diff(&unprivileged).to_sysctl_d(),
+            "b.changed = 2\n# d.readonly is read-only\ne.added = 3 4\n# c.removed was removed\n"
+        );
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn snapshot_capture() {
+        let root = crate::sys::tests::fake_root(
+            "snapshot_capture",
+            &[("kernel/ostype", "Fake\n"), ("vm/swappiness", "60\n")],
+        );
+        let before = Snapshot::from_ctls(crate::CtlIter::root_at(&root)).expect("from_ctls");
+        assert_eq!(before.len(), 2);
+        assert_eq!(
+            before.get("vm.swappiness").map(|e| e.value.as_str()),
+            Some("60")
+        );
+
+        std::fs::write(format!("{}/vm/swappiness", root), "10\n").expect("write");
+        let after = Snapshot::from_ctls(crate::CtlIter::root_at(&root)).expect("from_ctls");
+        assert_eq!(before.diff(&after).to_sysctl_d(), "vm.swappiness = 10\n");
+    }
+}
@@ -0,0 +1,492 @@
+// sysctl_conf.rs
+
+use crate::backend::{Backend, SystemBackend};
+use crate::ctl_error::SysctlError;
+use crate::traits::Sysctl;
+
+use std::path::{Path, PathBuf};
+
+/// The directories `SysctlConf::system` reads, highest priority first.
+pub const SYSCTL_D_DIRS: &[&str] = &[
+    "/etc/sysctl.d",
+    "/run/sysctl.d",
+    "/usr/local/lib/sysctl.d",
+    "/usr/lib/sysctl.d",
+];
+
+/// A single assignment read from a sysctl.conf file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfEntry {
+    /// The sysctl name or glob pattern, in dotted form.
+    pub key: String,
+
+    /// The value to assign.
+    pub value: String,
+
+    /// Set if the line was prefixed with `-`: failing to set the sysctl
+    /// is not an error.
+    pub ignore_failure: bool,
+
+    /// The file the entry was read from, if any.
+    pub file: Option<PathBuf>,
+
+    /// The line number of the entry, starting at 1.
+    pub line: usize,
+}
+
+impl ConfEntry {
+    /// Is the key a glob pattern?
+    pub fn is_glob(&self) -> bool {
+        self.key.contains(['*', '?', '['])
+    }
+}
+
+/// The outcome of applying a value to a single sysctl.
+#[derive(Debug)]
+pub struct ConfResult {
+    /// The name of the sysctl that was set.
+    pub name: String,
+
+    /// The value that was applied.
+    pub value: String,
+
+    /// Set if failures for this sysctl are to be ignored.
+    pub ignore_failure: bool,
+
+    /// The value read back after setting it, or the error.
+    pub result: Result<String, SysctlError>,
+}
+
+impl ConfResult {
+    /// Did setting the sysctl fail, and is that failure not ignored?
+    pub fn is_failure(&self) -> bool {
+        self.result.is_err() && !self.ignore_failure
+    }
+}
+
+/// A set of sysctl assignments in `sysctl.conf` syntax.
+///
+/// Each line holds an assignment `name = value`. Lines starting with `#`
+/// or `;` are comments. A name prefixed with `-` marks an assignment whose
+/// failure is ignored. Names may contain glob patterns such as
+/// `net.ipv4.conf.*.rp_filter`, and may be written in slash form such as
+/// `net/ipv4/conf/eth0.100/rp_filter` on Linux.
+///
+/// # Example
+/// ```
+/// let conf = sysctl::SysctlConf::parse("# Enable forwarding\nnet.ipv4.ip_forward = 1\n").unwrap();
+/// assert_eq!(conf.entries()[0].key, "net.ipv4.ip_forward");
+/// assert_eq!(conf.entries()[0].value, "1");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SysctlConf {
+    entries: Vec<ConfEntry>,
+}
+
+impl SysctlConf {
+    /// Parse assignments in `sysctl.conf` syntax.
+    pub fn parse(s: &str) -> Result<Self, SysctlError> {
+        SysctlConf::parse_source(s, None)
+    }
+
+    /// Read and parse a `sysctl.conf` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SysctlError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)?;
+        SysctlConf::parse_source(&s, Some(path))
+    }
+
+    /// Read the `*.conf` files in `dirs` the way `systemd-sysctl` does.
+    ///
+    /// `dirs` are given highest priority first. A file masks files with
+    /// the same name in directories of lower priority. The remaining files
+    /// are read in the lexicographic order of their names, so a later
+    /// assignment to the same sysctl overrides an earlier one.
+    pub fn from_dirs<P: AsRef<Path>>(dirs: &[P]) -> Result<Self, SysctlError> {
+        let mut files: std::collections::BTreeMap<std::ffi::OsString, PathBuf> =
+            std::collections::BTreeMap::new();
+        for dir in dirs {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "conf")
+                    && let Some(name) = path.file_name()
+                {
+                    files.entry(name.to_owned()).or_insert(path);
+                }
+            }
+        }
+
+        let mut conf = SysctlConf::default();
+        for path in files.values() {
+            conf.entries.extend(SysctlConf::from_file(path)?.entries);
+        }
+        Ok(conf)
+    }
+
+    /// Read the system configuration from `SYSCTL_D_DIRS`.
+    pub fn system() -> Result<Self, SysctlError> {
+        SysctlConf::from_dirs(SYSCTL_D_DIRS)
+    }
+
+    fn parse_source(s: &str, file: Option<&Path>) -> Result<Self, SysctlError> {
+        let mut entries = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| SysctlError::ConfSyntax {
+                    file: file.map(|f| f.display().to_string()).unwrap_or_default(),
+                    line: i + 1,
+                })?;
+            let key = key.trim();
+            let (key, ignore_failure) = match key.strip_prefix('-') {
+                Some(key) => (key.trim_start(), true),
+                None => (key, false),
+            };
+            entries.push(ConfEntry {
+                key: normalize(key),
+                value: value.trim().to_owned(),
+                ignore_failure,
+                file: file.map(Path::to_path_buf),
+                line: i + 1,
+            });
+        }
+        Ok(SysctlConf { entries })
+    }
+
+    /// Returns the entries in the order they were read.
+    pub fn entries(&self) -> &[ConfEntry] {
+        &self.entries
+    }
+
+    /// Returns the entries that take effect: of several assignments to the
+    /// same key, only the last one is kept, at the position it was read.
+    pub fn effective(&self) -> Vec<&ConfEntry> {
+        let mut effective: Vec<&ConfEntry> = vec![];
+        for entry in &self.entries {
+            effective.retain(|e| e.key != entry.key);
+            effective.push(entry);
+        }
+        effective
+    }
+
+    /// Apply the configuration with `Ctl::set_value_string`, and return the
+    /// outcome for every sysctl that was set.
+    pub fn apply(&self) -> Vec<ConfResult> {
+        self.apply_to(&SystemBackend)
+    }
+
+    /// Apply the configuration to the sysctls of `backend`.
+    pub fn apply_to<B: Backend>(&self, backend: &B) -> Vec<ConfResult> {
+        self.apply_with(|name| backend.ctl(name), || backend.iter())
+    }
+
+    /// Apply the configuration, looking up sysctls with `lookup` and
+    /// expanding glob patterns against the sysctls returned by `list`,
+    /// which is called at most once.
+    ///
+    /// Glob patterns are not applied to sysctls that are assigned
+    /// explicitly elsewhere in the configuration.
+    pub fn apply_with<C, L, I, F>(&self, lookup: L, list: F) -> Vec<ConfResult>
+    where
+        C: Sysctl,
+        L: Fn(&str) -> Result<C, SysctlError>,
+        I: Iterator<Item = Result<C, SysctlError>>,
+        F: Fn() -> I,
+    {
+        let effective = self.effective();
+        let mut results = vec![];
+
+        // The tree is walked once, and only if there are glob patterns.
+        let mut listed: Option<Vec<(String, C)>> = None;
+        for entry in &effective {
+            if !entry.is_glob() {
+                let result = lookup(&entry.key).and_then(|c| c.set_value_string(&entry.value));
+                results.push(ConfResult {
+                    name: entry.key.clone(),
+                    value: entry.value.clone(),
+                    ignore_failure: entry.ignore_failure,
+                    result,
+                });
+                continue;
+            }
+
+            let listed = listed.get_or_insert_with(|| {
+                list()
+                    .filter_map(|ctl| {
+                        let ctl = ctl.ok()?;
+                        Some((ctl.name().ok()?, ctl))
+                    })
+                    .collect()
+            });
+            for (name, ctl) in listed.iter() {
+                if !glob_match(&entry.key, name)
+                    || effective.iter().any(|e| !e.is_glob() && &e.key == name)
+                {
+                    continue;
+                }
+                let result = ctl.set_value_string(&entry.value);
+                results.push(ConfResult {
+                    name: name.clone(),
+                    value: entry.value.clone(),
+                    ignore_failure: entry.ignore_failure,
+                    result,
+                });
+            }
+        }
+        results
+    }
+}
+
+// Bring a name in slash form into dotted form.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub(crate) fn normalize(key: &str) -> String {
+    crate::CtlName::from(key).to_string()
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+pub(crate) fn normalize(key: &str) -> String {
+    key.to_owned()
+}
+
+/// Match `name` against a glob `pattern` like `fnmatch(3)` without flags:
+/// `*` matches any sequence of characters including dots, `?` matches a
+/// single character and `[...]` matches a set of characters.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    // Position after the last `*` and the name position it matched up to.
+    let mut star: Option<(usize, usize)> = None;
+
+    while ni < n.len() {
+        let step = match p.get(pi) {
+            Some('*') => {
+                star = Some((pi + 1, ni));
+                pi += 1;
+                continue;
+            }
+            Some('?') => Some(pi + 1),
+            Some('[') => match_class(&p, pi, n[ni]),
+            Some(c) if *c == n[ni] => Some(pi + 1),
+            _ => None,
+        };
+        match (step, star) {
+            (Some(next), _) => {
+                pi = next;
+                ni += 1;
+            }
+            (None, Some((sp, sn))) => {
+                pi = sp;
+                ni = sn + 1;
+                star = Some((sp, sn + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+// Match `c` against the class starting at `p[start] == '['`. Returns the
+// position after the class if it matches.
+fn match_class(p: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start + 1;
+    let negate = matches!(p.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < p.len() && (first || p[i] != ']') {
+        first = false;
+        if i + 2 < p.len() && p[i + 1] == '-' && p[i + 2] != ']' {
+            matched |= p[i] <= c && c <= p[i + 2];
+            i += 3;
+        } else {
+            matched |= p[i] == c;
+            i += 1;
+        }
+    }
+    if i >= p.len() {
+        // No closing bracket, so the `[` is a literal.
+        return (c == '[').then_some(start + 1);
+    }
+    (matched != negate).then_some(i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SysctlConf, glob_match};
+
+    #[test]
+    fn conf_parse() {
+        let conf = SysctlConf::parse(
+            "# comment\n; another comment\n\n  kernel.printk = 4 4 1 7 \n-net.ipv4.conf.*.rp_filter=2\n",
+        )
+        .expect("parse");
+        let entries = conf.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, "kernel.printk");
+        assert_eq!(entries[0].value, "4 4 1 7");
+        assert!(!entries[0].ignore_failure);
+        assert_eq!(entries[0].line, 4);
+        assert_eq!(entries[1].key, "net.ipv4.conf.*.rp_filter");
+        assert_eq!(entries[1].value, "2");
+        assert!(entries[1].ignore_failure);
+        assert!(entries[1].is_glob());
+    }
+
+    #[test]
+    fn conf_parse_error() {
+        let r = SysctlConf::parse("kernel.printk = 4\nkernel.sysrq\n");
+        assert!(matches!(
+            r,
+            Err(crate::SysctlError::ConfSyntax { line: 2, .. })
+        ));
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn conf_parse_slash() {
+        let conf = SysctlConf::parse("net/ipv4/conf/eth0.100/rp_filter = 1").expect("parse");
+        assert_eq!(conf.entries()[0].key, "net.ipv4.conf.eth0/100.rp_filter");
+    }
+
+    #[test]
+    fn conf_effective() {
+        let conf = SysctlConf::parse("a.b = 1\nc.d = 2\na.b = 3\n").expect("parse");
+        let effective: Vec<(&str, &str)> = conf
+            .effective()
+            .iter()
+            .map(|e| (e.key.as_str(), e.value.as_str()))
+            .collect();
+        assert_eq!(effective, [("c.d", "2"), ("a.b", "3")]);
+    }
+
+    #[test]
+    fn conf_from_dirs() {
+        let base = std::env::temp_dir().join(format!("sysctl-rs-conf-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (etc, usr) = (base.join("etc"), base.join("usr"));
+        std::fs::create_dir_all(&etc).expect("create_dir_all");
+        std::fs::create_dir_all(&usr).expect("create_dir_all");
+        std::fs::write(usr.join("10-default.conf"), "a.b = 1\nc.d = 1\n").expect("write");
+        std::fs::write(usr.join("50-masked.conf"), "e.f = 1\n").expect("write");
+        std::fs::write(usr.join("README"), "not = read\n").expect("write");
+        std::fs::write(etc.join("50-masked.conf"), "e.f = 2\n").expect("write");
+        std::fs::write(etc.join("90-local.conf"), "a.b = 3\n").expect("write");
+
+        let conf = SysctlConf::from_dirs(&[&etc, &base.join("missing"), &usr]).expect("from_dirs");
+        let effective: Vec<(&str, &str)> = conf
+            .effective()
+            .iter()
+            .map(|e| (e.key.as_str(), e.value.as_str()))
+            .collect();
+        assert_eq!(effective, [("c.d", "1"), ("e.f", "2"), ("a.b", "3")]);
+        assert_eq!(
+            conf.entries()[2].file.as_deref(),
+            Some(etc.join("50-masked.conf").as_path())
+        );
+    }
+
+    #[test]
+    fn conf_glob_match() {
+        assert!(glob_match(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.eth0.rp_filter"
+        ));
+        assert!(glob_match(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.eth0/100.rp_filter"
+        ));
+        assert!(!glob_match(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.eth0.forwarding"
+        ));
+        assert!(glob_match("vm.dirty_*", "vm.dirty_ratio"));
+        assert!(glob_match("kernel.?id_max", "kernel.pid_max"));
+        assert!(glob_match("net.ipv[46].ip_forward", "net.ipv4.ip_forward"));
+        assert!(!glob_match(
+            "net.ipv[!46].ip_forward",
+            "net.ipv4.ip_forward"
+        ));
+        assert!(glob_match("net.ipv[0-9].ip_forward", "net.ipv6.ip_forward"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("a", ""));
+        assert!(glob_match("a[", "a["));
+    }
+
+    #[test]
+    fn conf_apply_to_mock() {
+        let mock = crate::MockBackend::new();
+        mock.insert("net.ipv4.conf.all.rp_filter", crate::CtlValue::Int(0));
+        mock.insert("net.ipv4.conf.lo.rp_filter", crate::CtlValue::Int(0));
+        let conf = SysctlConf::parse("net.ipv4.conf.*.rp_filter = 1\n").expect("parse");
+        let results = conf.apply_to(&mock);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        assert_eq!(
+            mock.get("net.ipv4.conf.lo.rp_filter"),
+            Some(crate::CtlValue::Int(1))
+        );
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn conf_apply() {
+        let root = crate::sys::tests::fake_root(
+            "conf_apply",
+            &[
+                ("net/ipv4/conf/all/rp_filter", "0\n"),
+                ("net/ipv4/conf/eth0/rp_filter", "0\n"),
+                ("net/ipv4/conf/lo/rp_filter", "0\n"),
+                ("kernel/sysrq", "16\n"),
+            ],
+        );
+        let conf = SysctlConf::parse(
+            "net.ipv4.conf.*.rp_filter = 2\nnet.ipv4.conf.lo.rp_filter = 0\nkernel.sysrq = 1\n-kernel.missing = 1\nvm.missing = 1\nfs.*.missing = 1\n",
+        )
+        .expect("parse");
+
+        let walks = std::cell::Cell::new(0);
+        let results = conf.apply_with(
+            |name| crate::Ctl::new_in(&root, name),
+            || {
+                walks.set(walks.get() + 1);
+                crate::CtlIter::root_at(&root)
+            },
+        );
+        assert_eq!(walks.get(), 1);
+        let outcome: Vec<(&str, bool, bool)> = results
+            .iter()
+            .map(|r| (r.name.as_str(), r.result.is_ok(), r.is_failure()))
+            .collect();
+        assert_eq!(
+            outcome,
+            [
+                ("net.ipv4.conf.all.rp_filter", true, false),
+                ("net.ipv4.conf.eth0.rp_filter", true, false),
+                ("net.ipv4.conf.lo.rp_filter", true, false),
+                ("kernel.sysrq", true, false),
+                ("kernel.missing", false, false),
+                ("vm.missing", false, true),
+            ]
+        );
+        let value = |path: &str| {
+            std::fs::read_to_string(format!("{}/{}", root, path))
+                .unwrap()
+                .trim()
+                .to_owned()
+        };
+        assert_eq!(value("net/ipv4/conf/eth0/rp_filter"), "2");
+        assert_eq!(value("net/ipv4/conf/lo/rp_filter"), "0");
+    }
+}
@@ -2,6 +2,7 @@
 
 use byteorder::ByteOrder;
 use crate::ctl_error::SysctlError;
+use crate::ctl_format::CtlFormat;
 use crate::ctl_info::CtlInfo;
 use crate::ctl_type::CtlType;
 use crate::ctl_value::CtlValue;
@@ -24,6 +25,7 @@ use std::f32;
 /// ```
 /// Not available on MacOS
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Temperature {
     value: f32, // Kelvin
 }
@@ -40,14 +42,9 @@ impl Temperature {
 }
 
 pub fn temperature(info: &CtlInfo, val: &[u8]) -> Result<CtlValue, SysctlError> {
-    let prec: u32 = {
-        match info.fmt.len() {
-            l if l > 2 => match info.fmt[2..3].parse::<u32>() {
-                Ok(x) if x <= 9 => x,
-                _ => 1,
-            },
-            _ => 1,
-        }
+    let prec: u32 = match info.format() {
+        CtlFormat::Temperature { precision } => precision.into(),
+        _ => 1,
     };
 
     let base = 10u32.pow(prec) as f32;
@@ -112,4 +109,20 @@ mod tests_freebsd {
         let tt = t.as_temperature().unwrap();
         assert!(tt.kelvin() - 333.0 < 0.1);
     }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn ctl_temperature_serde() {
+        let info = crate::CtlInfo {
+            ctl_type: crate::CtlType::Int,
+            fmt: "IK".into(),
+            flags: 0,
+        };
+        let mut val = vec![];
+        val.write_i32::<byteorder::LittleEndian>(3331)
+            .expect("Error parsing value to byte array");
+
+        let t = super::temperature(&info, &val).unwrap();
+        let json = serde_json::to_string(&t).unwrap();
+        assert_eq!(serde_json::from_str::<crate::CtlValue>(&json).unwrap(), t);
+    }
 }
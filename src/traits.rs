@@ -150,6 +150,10 @@ pub trait Sysctl {
     /// Sets the value of a sysctl.
     /// Fetches and returns the new value if successful, or returns a
     /// SysctlError on failure.
+    ///
+    /// On Linux, write-only sysctls such as `vm.drop_caches` cannot be
+    /// fetched, and `CtlValue::WriteOnly` is returned once the write
+    /// succeeded.
     /// # Example
     /// ```
     /// use sysctl::Sysctl;
@@ -170,6 +174,9 @@ pub trait Sysctl {
     /// Sets the value of a sysctl with input as string.
     /// Fetches and returns the new value if successful, or returns a
     /// SysctlError on failure.
+    ///
+    /// For write-only sysctls the string of `CtlValue::WriteOnly`,
+    /// `[Write-only]`, is returned once the write succeeded.
     /// # Example
     /// ```
     /// use sysctl::Sysctl;
@@ -201,6 +208,24 @@ pub trait Sysctl {
     /// ```
     fn flags(&self) -> Result<CtlFlags, SysctlError>;
 
+    /// Returns whether the sysctl can be written at all.
+    ///
+    /// By default this is whether `flags()` contains `CtlFlags::WR`. On
+    /// Linux, where `CtlFlags::WR` tells whether the calling process may
+    /// write the sysctl, it is decided from the mode of its file instead,
+    /// and is the same for every process.
+    ///
+    /// # Example
+    /// ```
+    /// # use sysctl::Sysctl;
+    /// if let Ok(ctl) = sysctl::Ctl::new("kern.ostype") {
+    ///     assert!(!ctl.is_writable().unwrap());
+    /// }
+    /// ```
+    fn is_writable(&self) -> Result<bool, SysctlError> {
+        Ok(self.flags()?.contains(CtlFlags::WR))
+    }
+
     #[allow(clippy::needless_doctest_main)]
     /// Returns a Result containing the control metadata for a sysctl.
     ///
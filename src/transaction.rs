@@ -0,0 +1,279 @@
+// transaction.rs
+
+use crate::ctl_error::SysctlError;
+use crate::ctl_value::CtlValue;
+use crate::traits::Sysctl;
+
+/// A sysctl that could not be restored to its original value.
+#[derive(Debug)]
+pub struct RollbackFailure {
+    /// The name of the sysctl.
+    pub name: String,
+
+    /// The value it should have been restored to.
+    pub value: CtlValue,
+
+    /// The error returned when restoring it.
+    pub error: SysctlError,
+}
+
+/// A set of sysctl writes that are undone together.
+///
+/// Each write first records the original value of the sysctl. If a write
+/// fails, all sysctls changed so far are restored in reverse order and the
+/// error is returned as `SysctlError::TransactionFailed`. Dropping a
+/// transaction without calling `commit` restores them as well; call
+/// `rollback` instead to learn about sysctls that could not be restored.
+///
+/// Sysctls whose original value cannot be written back, such as write-only
+/// sysctls like `vm.drop_caches` and temperatures on FreeBSD, are rejected
+/// with `SysctlError::NotRestorable` before anything is written. The
+/// transaction is left as it was.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let mut tx = sysctl::Transaction::new();
+/// tx.set_value_string(sysctl::Ctl::new("net.core.rmem_max").unwrap(), "16777216").unwrap();
+/// tx.set_value_string(sysctl::Ctl::new("net.core.wmem_max").unwrap(), "16777216").unwrap();
+/// tx.commit();
+/// ```
+pub struct Transaction<C: Sysctl> {
+    applied: Vec<(C, CtlValue)>,
+    committed: bool,
+}
+
+impl<C: Sysctl> Default for Transaction<C> {
+    fn default() -> Self {
+        Transaction::new()
+    }
+}
+
+impl<C: Sysctl> Transaction<C> {
+    pub fn new() -> Self {
+        Transaction {
+            applied: vec![],
+            committed: false,
+        }
+    }
+
+    /// Set the value of `ctl`, or roll back the transaction if that fails.
+    pub fn set_value(&mut self, ctl: C, value: CtlValue) -> Result<CtlValue, SysctlError> {
+        self.apply(ctl, |ctl| ctl.set_value(value))
+    }
+
+    /// Set the value of `ctl` from a string, or roll back the transaction
+    /// if that fails.
+    pub fn set_value_string(&mut self, ctl: C, value: &str) -> Result<String, SysctlError> {
+        self.apply(ctl, |ctl| ctl.set_value_string(value))
+    }
+
+    fn apply<F, R>(&mut self, ctl: C, set: F) -> Result<R, SysctlError>
+    where
+        F: FnOnce(&C) -> Result<R, SysctlError>,
+    {
+        let original = match ctl.value() {
+            Ok(v) if !is_restorable(&v) => return Err(SysctlError::NotRestorable(ctl.name()?)),
+            Err(SysctlError::NoReadAccess) => return Err(SysctlError::NotRestorable(ctl.name()?)),
+            r => r,
+        };
+        let result = original.and_then(|original| set(&ctl).map(|r| (original, r)));
+        match result {
+            Ok((original, r)) => {
+                self.applied.push((ctl, original));
+                Ok(r)
+            }
+            Err(error) => Err(SysctlError::TransactionFailed {
+                name: ctl.name().unwrap_or_default(),
+                error: Box::new(error),
+                rollback_failures: self.restore(),
+            }),
+        }
+    }
+
+    /// Returns the number of sysctls written so far.
+    pub fn len(&self) -> usize {
+        self.applied.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty()
+    }
+
+    /// Keep the values written so far.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// Restore the original values of all sysctls written so far.
+    pub fn rollback(mut self) -> Result<(), SysctlError> {
+        let failures = self.restore();
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(SysctlError::RollbackFailed(failures)),
+        }
+    }
+
+    fn restore(&mut self) -> Vec<RollbackFailure> {
+        let mut failures = vec![];
+        while let Some((ctl, value)) = self.applied.pop() {
+            if let Err(error) = ctl.set_value(value.clone()) {
+                failures.push(RollbackFailure {
+                    name: ctl.name().unwrap_or_default(),
+                    value,
+                    error,
+                });
+            }
+        }
+        failures
+    }
+}
+
+// Return whether `set_value` accepts `value` to restore it.
+fn is_restorable(value: &CtlValue) -> bool {
+    match value {
+        CtlValue::None | CtlValue::WriteOnly | CtlValue::Node(_) => false,
+        #[cfg(target_os = "freebsd")]
+        CtlValue::Temperature(_) => false,
+        _ => true,
+    }
+}
+
+impl<C: Sysctl> Drop for Transaction<C> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.restore();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transaction;
+    use crate::{Backend, CtlFlags, CtlValue, MockBackend, MockEntry, SysctlError};
+
+    #[test]
+    fn transaction_write_only() {
+        let mock = MockBackend::new();
+        mock.insert("vm.swappiness", CtlValue::Int(60));
+        mock.insert(
+            "vm.drop_caches",
+            MockEntry::new(CtlValue::Int(0)).with_flags(CtlFlags::WR),
+        );
+        let mut tx = Transaction::new();
+        tx.set_value(mock.ctl("vm.swappiness").unwrap(), CtlValue::Int(10))
+            .expect("set_value");
+
+        // The write-only sysctl is rejected without undoing the first
+        // write.
+        let r = tx.set_value(mock.ctl("vm.drop_caches").unwrap(), CtlValue::Int(3));
+        assert!(matches!(r, Err(SysctlError::NotRestorable(name)) if name == "vm.drop_caches"));
+        assert_eq!(mock.get("vm.drop_caches"), Some(CtlValue::Int(0)));
+        assert_eq!(tx.len(), 1);
+        assert_eq!(mock.get("vm.swappiness"), Some(CtlValue::Int(10)));
+        tx.rollback().expect("rollback");
+        assert_eq!(mock.get("vm.swappiness"), Some(CtlValue::Int(60)));
+    }
+
+    #[cfg(target_os = "freebsd")]
+    #[test]
+    fn transaction_temperature() {
+        let info = crate::CtlInfo {
+            ctl_type: crate::CtlType::Int,
+            fmt: "IK".to_owned(),
+            flags: 0,
+        };
+        let temperature =
+            crate::temperature::temperature(&info, &3181i32.to_le_bytes()).expect("temperature");
+        let mock = MockBackend::new();
+        mock.insert("dev.cpu.0.temperature", temperature.clone());
+        let mut tx = Transaction::new();
+        let r = tx.set_value(mock.ctl("dev.cpu.0.temperature").unwrap(), CtlValue::Int(0));
+        assert!(matches!(r, Err(SysctlError::NotRestorable(_))));
+        assert_eq!(mock.get("dev.cpu.0.temperature"), Some(temperature));
+        assert!(tx.is_empty());
+    }
+}
+
+#[cfg(all(test, any(target_os = "android", target_os = "linux")))]
+mod tests_linux {
+    use super::Transaction;
+    use crate::{Ctl, Sysctl, SysctlError};
+
+    fn fake_root(name: &str) -> String {
+        crate::sys::tests::fake_root(name, &[("vm/a", "10\n"), ("vm/b", "20\n")])
+    }
+
+    fn read(root: &str, name: &str) -> String {
+        Ctl::new_in(root, name)
+            .and_then(|c| c.value_string())
+            .expect("value_string")
+    }
+
+    #[test]
+    fn transaction_commit() {
+        let root = fake_root("transaction_commit");
+        let mut tx = Transaction::new();
+        tx.set_value_string(Ctl::new_in(&root, "vm.a").unwrap(), "11")
+            .expect("set_value_string");
+        tx.set_value_string(Ctl::new_in(&root, "vm.b").unwrap(), "21")
+            .expect("set_value_string");
+        assert_eq!(tx.len(), 2);
+        tx.commit();
+        assert_eq!(read(&root, "vm.a"), "11");
+        assert_eq!(read(&root, "vm.b"), "21");
+    }
+
+    #[test]
+    fn transaction_drop() {
+        let root = fake_root("transaction_drop");
+        {
+            let mut tx = Transaction::new();
+            tx.set_value_string(Ctl::new_in(&root, "vm.a").unwrap(), "11")
+                .expect("set_value_string");
+            assert_eq!(read(&root, "vm.a"), "11");
+        }
+        assert_eq!(read(&root, "vm.a"), "10");
+    }
+
+    #[test]
+    fn transaction_failed() {
+        let root = fake_root("transaction_failed");
+        let mut tx = Transaction::new();
+        tx.set_value_string(Ctl::new_in(&root, "vm.a").unwrap(), "11")
+            .expect("set_value_string");
+        tx.set_value_string(Ctl::new_in(&root, "vm.b").unwrap(), "21")
+            .expect("set_value_string");
+
+        // Make restoring vm.b fail.
+        std::fs::remove_file(format!("{}/vm/b", root)).expect("remove_file");
+        let r = tx.set_value_string(Ctl::new_in(&root, "vm").unwrap(), "1");
+        match r {
+            Err(SysctlError::TransactionFailed {
+                name,
+                rollback_failures,
+                ..
+            }) => {
+                assert_eq!(name, "vm");
+                assert_eq!(rollback_failures.len(), 1);
+                assert_eq!(rollback_failures[0].name, "vm.b");
+                assert_eq!(rollback_failures[0].value.to_string(), "20");
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(tx.is_empty());
+        assert_eq!(read(&root, "vm.a"), "10");
+    }
+
+    #[test]
+    fn transaction_rollback() {
+        let root = fake_root("transaction_rollback");
+        let mut tx = Transaction::new();
+        tx.set_value_string(Ctl::new_in(&root, "vm.a").unwrap(), "11")
+            .expect("set_value_string");
+        tx.set_value_string(Ctl::new_in(&root, "vm.a").unwrap(), "12")
+            .expect("set_value_string");
+        tx.rollback().expect("rollback");
+        assert_eq!(read(&root, "vm.a"), "10");
+    }
+}
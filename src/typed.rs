@@ -0,0 +1,439 @@
+// typed.rs
+
+use crate::ctl_error::SysctlError;
+use crate::ctl_format::{CtlFormat, IntWidth};
+use crate::ctl_info::CtlInfo;
+use crate::ctl_type::CtlType;
+use crate::ctl_value::CtlValue;
+use crate::traits::Sysctl;
+
+use std::marker::PhantomData;
+
+/// A Rust type that sysctl values can be converted to.
+pub trait FromSysctl: Sized {
+    /// Can values of sysctls of type `ctl_type` be converted?
+    fn accepts(ctl_type: CtlType) -> bool;
+
+    /// Convert a value as returned by `Sysctl::value`.
+    fn from_sysctl(value: CtlValue) -> Result<Self, SysctlError>;
+}
+
+/// A Rust type that can be written to sysctls.
+pub trait ToSysctl {
+    /// Convert to a value that can be written to a sysctl of type
+    /// `ctl_type` with `Sysctl::set_value`.
+    fn to_sysctl(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {$(
+        impl FromSysctl for $t {
+            fn accepts(ctl_type: CtlType) -> bool {
+                is_integer(ctl_type) || ctl_type == CtlType::String
+            }
+
+            fn from_sysctl(value: CtlValue) -> Result<Self, SysctlError> {
+                let n = match value {
+                    CtlValue::String(s) => {
+                        return s.trim().parse().map_err(|_| SysctlError::ParseError);
+                    }
+                    CtlValue::Int(n) => i128::from(n),
+                    CtlValue::Uint(n) => i128::from(n),
+                    CtlValue::Long(n) => i128::from(n),
+                    CtlValue::Ulong(n) => i128::from(n),
+                    CtlValue::S8(n) => i128::from(n),
+                    CtlValue::S16(n) => i128::from(n),
+                    CtlValue::S32(n) => i128::from(n),
+                    CtlValue::S64(n) => i128::from(n),
+                    CtlValue::U8(n) => i128::from(n),
+                    CtlValue::U16(n) => i128::from(n),
+                    CtlValue::U32(n) => i128::from(n),
+                    CtlValue::U64(n) => i128::from(n),
+                    _ => return Err(SysctlError::ExtractionError),
+                };
+                <$t>::try_from(n).map_err(|_| SysctlError::ParseError)
+            }
+        }
+
+        impl ToSysctl for $t {
+            fn to_sysctl(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+                integer_value(i128::from(*self), ctl_type)
+            }
+        }
+    )*};
+}
+
+impl_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+fn is_integer(ctl_type: CtlType) -> bool {
+    matches!(
+        ctl_type,
+        CtlType::Int
+            | CtlType::Uint
+            | CtlType::Long
+            | CtlType::Ulong
+            | CtlType::S8
+            | CtlType::S16
+            | CtlType::S32
+            | CtlType::S64
+            | CtlType::U8
+            | CtlType::U16
+            | CtlType::U32
+            | CtlType::U64
+    )
+}
+
+// Convert `n` to a value of type `ctl_type`, failing if it is out of range.
+fn integer_value(n: i128, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+    let range = |_| SysctlError::InvalidValue {
+        name: String::new(),
+        value: n.to_string(),
+    };
+    Ok(match ctl_type {
+        CtlType::String => CtlValue::String(n.to_string()),
+        CtlType::Int => CtlValue::Int(n.try_into().map_err(range)?),
+        CtlType::Uint => CtlValue::Uint(n.try_into().map_err(range)?),
+        CtlType::Long => CtlValue::Long(n.try_into().map_err(range)?),
+        CtlType::Ulong => CtlValue::Ulong(n.try_into().map_err(range)?),
+        CtlType::S8 => CtlValue::S8(n.try_into().map_err(range)?),
+        CtlType::S16 => CtlValue::S16(n.try_into().map_err(range)?),
+        CtlType::S32 => CtlValue::S32(n.try_into().map_err(range)?),
+        CtlType::S64 => CtlValue::S64(n.try_into().map_err(range)?),
+        CtlType::U8 => CtlValue::U8(n.try_into().map_err(range)?),
+        CtlType::U16 => CtlValue::U16(n.try_into().map_err(range)?),
+        CtlType::U32 => CtlValue::U32(n.try_into().map_err(range)?),
+        CtlType::U64 => CtlValue::U64(n.try_into().map_err(range)?),
+        _ => return Err(SysctlError::MissingImplementation),
+    })
+}
+
+/// Integer sysctls convert to `true` if they are not 0.
+impl FromSysctl for bool {
+    fn accepts(ctl_type: CtlType) -> bool {
+        i64::accepts(ctl_type)
+    }
+
+    fn from_sysctl(value: CtlValue) -> Result<Self, SysctlError> {
+        match value {
+            CtlValue::Ulong(n) | CtlValue::U64(n) => Ok(n != 0),
+            value => i64::from_sysctl(value).map(|n| n != 0),
+        }
+    }
+}
+
+impl ToSysctl for bool {
+    fn to_sysctl(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+        u8::from(*self).to_sysctl(ctl_type)
+    }
+}
+
+impl FromSysctl for String {
+    fn accepts(ctl_type: CtlType) -> bool {
+        ctl_type == CtlType::String
+    }
+
+    fn from_sysctl(value: CtlValue) -> Result<Self, SysctlError> {
+        match value {
+            CtlValue::String(s) => Ok(s),
+            _ => Err(SysctlError::ExtractionError),
+        }
+    }
+}
+
+impl ToSysctl for String {
+    fn to_sysctl(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+        match ctl_type {
+            CtlType::String => Ok(CtlValue::String(self.clone())),
+            _ => Err(SysctlError::MissingImplementation),
+        }
+    }
+}
+
+/// Sysctls holding several fields, such as `net.ipv4.tcp_rmem` on Linux.
+/// On Linux the fields are separated by whitespace.
+impl<T: FromSysctl> FromSysctl for Vec<T> {
+    fn accepts(ctl_type: CtlType) -> bool {
+        ctl_type == CtlType::List || T::accepts(ctl_type)
+    }
+
+    fn from_sysctl(value: CtlValue) -> Result<Self, SysctlError> {
+        match value {
+            CtlValue::List(values) => values.into_iter().map(T::from_sysctl).collect(),
+            CtlValue::String(s) => s
+                .split_whitespace()
+                .map(|f| T::from_sysctl(CtlValue::String(f.to_owned())))
+                .collect(),
+            value => Ok(vec![T::from_sysctl(value)?]),
+        }
+    }
+}
+
+/// Lists are written as a `CtlValue::List` of strings to sysctls of type
+/// `CtlType::List`, and as strings with the fields separated by tabs
+/// otherwise.
+impl<T: ToSysctl> ToSysctl for Vec<T> {
+    fn to_sysctl(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+        list_value(self, ctl_type)
+    }
+}
+
+/// Sysctls holding exactly `N` fields.
+impl<T: FromSysctl, const N: usize> FromSysctl for [T; N] {
+    fn accepts(ctl_type: CtlType) -> bool {
+        Vec::<T>::accepts(ctl_type)
+    }
+
+    fn from_sysctl(value: CtlValue) -> Result<Self, SysctlError> {
+        Vec::<T>::from_sysctl(value)?
+            .try_into()
+            .map_err(|_| SysctlError::ParseError)
+    }
+}
+
+impl<T: ToSysctl, const N: usize> ToSysctl for [T; N] {
+    fn to_sysctl(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+        list_value(self, ctl_type)
+    }
+}
+
+fn list_value<T: ToSysctl>(values: &[T], ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+    match (ctl_type, values) {
+        (CtlType::List, _) => Ok(CtlValue::List(
+            values
+                .iter()
+                .map(|v| v.to_sysctl(CtlType::String))
+                .collect::<Result<_, _>>()?,
+        )),
+        (CtlType::String, _) => {
+            let fields = values
+                .iter()
+                .map(|v| v.to_sysctl(CtlType::String).map(|v| v.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(CtlValue::String(fields.join("\t")))
+        }
+        (ctl_type, [value]) => value.to_sysctl(ctl_type),
+        _ => Err(SysctlError::MissingImplementation),
+    }
+}
+
+/// A sysctl whose value is read and written as the Rust type `T`.
+///
+/// The type of the sysctl is checked against `T` once, when the
+/// `TypedCtl` is constructed. Sysctls on Linux are strings, so their
+/// current value is parsed instead, unless it cannot be read.
+///
+/// # Example
+/// ```no_run
+/// let swappiness = sysctl::TypedCtl::<u32>::new("vm.swappiness").unwrap();
+/// let value: u32 = swappiness.get().unwrap();
+/// swappiness.set(&(value / 2)).unwrap();
+///
+/// let rmem = sysctl::TypedCtl::<Vec<u64>>::new("net.ipv4.tcp_rmem").unwrap();
+/// let forwarding = sysctl::TypedCtl::<bool>::new("net.ipv4.ip_forward").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedCtl<T, C = crate::Ctl> {
+    ctl: C,
+    ctl_type: CtlType,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: FromSysctl> TypedCtl<T> {
+    /// Look up the sysctl `name` and check that it holds a `T`.
+    pub fn new(name: &str) -> Result<Self, SysctlError> {
+        TypedCtl::from_ctl(crate::Ctl::new(name)?)
+    }
+}
+
+impl<T: FromSysctl, C: Sysctl> TypedCtl<T, C> {
+    /// Check that `ctl` holds a `T`. Use this with the `Ctl` of a
+    /// `Backend`.
+    pub fn from_ctl(ctl: C) -> Result<Self, SysctlError> {
+        let info = ctl.info()?;
+        let ctl_type = value_type(&info);
+        let mismatch = || SysctlError::TypeMismatch {
+            name: ctl.name().unwrap_or_default(),
+            expected: std::any::type_name::<T>().to_owned(),
+            found: ctl_type,
+        };
+        // Integer sysctls in a temperature format are read as
+        // `CtlValue::Temperature`, which no integer type accepts.
+        let temperature = matches!(info.format(), CtlFormat::Temperature { .. });
+        if !T::accepts(ctl_type) || (temperature && is_integer(ctl_type)) {
+            return Err(mismatch());
+        }
+        if ctl_type == CtlType::String {
+            match ctl.value() {
+                Ok(value) => {
+                    T::from_sysctl(value).map_err(|_| mismatch())?;
+                }
+                Err(SysctlError::NoReadAccess) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(TypedCtl {
+            ctl,
+            ctl_type,
+            marker: PhantomData,
+        })
+    }
+
+    /// Read the value.
+    pub fn get(&self) -> Result<T, SysctlError> {
+        T::from_sysctl(self.ctl.value()?)
+    }
+
+    /// Returns the underlying sysctl.
+    pub fn ctl(&self) -> &C {
+        &self.ctl
+    }
+}
+
+impl<T: ToSysctl, C: Sysctl> TypedCtl<T, C> {
+    /// Write the value.
+    pub fn set(&self, value: &T) -> Result<(), SysctlError> {
+        let value = value.to_sysctl(self.ctl_type).map_err(|e| match e {
+            SysctlError::InvalidValue { value, .. } => SysctlError::InvalidValue {
+                name: self.ctl.name().unwrap_or_default(),
+                value,
+            },
+            e => e,
+        })?;
+        self.ctl.set_value(value).map(|_| ())
+    }
+}
+
+// The type `Sysctl::set_value` expects. On macOS, integer sysctls are
+// told apart by their format.
+fn value_type(info: &CtlInfo) -> CtlType {
+    match (info.ctl_type, info.format()) {
+        (CtlType::Int, CtlFormat::Integer { width, signed }) => match (width, signed) {
+            (IntWidth::Int, false) => CtlType::Uint,
+            (IntWidth::Long, true) => CtlType::Long,
+            (IntWidth::Long, false) => CtlType::Ulong,
+            _ => CtlType::Int,
+        },
+        (ctl_type, _) => ctl_type,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FromSysctl, ToSysctl, TypedCtl};
+    use crate::{Backend, CtlFlags, CtlType, CtlValue, MockBackend, MockEntry, SysctlError};
+
+    #[test]
+    fn typed_conversions() {
+        assert_eq!(
+            u32::from_sysctl(CtlValue::String("60\n".to_owned())).unwrap(),
+            60
+        );
+        assert_eq!(u32::from_sysctl(CtlValue::Int(60)).unwrap(), 60);
+        assert!(u32::from_sysctl(CtlValue::Int(-1)).is_err());
+        assert!(bool::from_sysctl(CtlValue::Ulong(1)).unwrap());
+        assert!(!bool::from_sysctl(CtlValue::String("0".to_owned())).unwrap());
+        assert_eq!(
+            Vec::<u64>::from_sysctl(CtlValue::String("4096\t131072\t6291456".to_owned())).unwrap(),
+            [4096, 131072, 6291456]
+        );
+        assert_eq!(
+            vec![1u64, 2].to_sysctl(CtlType::String).unwrap(),
+            CtlValue::String("1\t2".to_owned())
+        );
+        assert_eq!(300u32.to_sysctl(CtlType::Int).unwrap(), CtlValue::Int(300));
+        assert!(300u32.to_sysctl(CtlType::U8).is_err());
+        assert_eq!(true.to_sysctl(CtlType::Uint).unwrap(), CtlValue::Uint(1));
+    }
+
+    #[test]
+    fn typed_ctl_mock() {
+        let mock = MockBackend::new();
+        mock.insert("vm.swappiness", CtlValue::Int(60));
+        mock.insert("net.ipv4.ip_forward", CtlValue::Uint(0));
+        mock.insert(
+            "net.ipv4.tcp_rmem",
+            CtlValue::List(vec![CtlValue::U64(4096), CtlValue::U64(131072)]),
+        );
+        mock.insert("kernel.ostype", CtlValue::String("Linux".to_owned()));
+        mock.insert(
+            "vm.drop_caches",
+            MockEntry::new(CtlValue::String("0".to_owned())).with_flags(CtlFlags::WR),
+        );
+
+        let swappiness = TypedCtl::<u32, _>::from_ctl(mock.ctl("vm.swappiness").unwrap()).unwrap();
+        assert_eq!(swappiness.get().unwrap(), 60);
+        swappiness.set(&10).unwrap();
+        assert_eq!(mock.get("vm.swappiness"), Some(CtlValue::Int(10)));
+        assert!(matches!(
+            swappiness.set(&u32::MAX),
+            Err(SysctlError::InvalidValue { name, .. }) if name == "vm.swappiness"
+        ));
+
+        let forward =
+            TypedCtl::<bool, _>::from_ctl(mock.ctl("net.ipv4.ip_forward").unwrap()).unwrap();
+        forward.set(&true).unwrap();
+        assert!(forward.get().unwrap());
+        assert_eq!(mock.get("net.ipv4.ip_forward"), Some(CtlValue::Uint(1)));
+
+        let rmem =
+            TypedCtl::<Vec<u64>, _>::from_ctl(mock.ctl("net.ipv4.tcp_rmem").unwrap()).unwrap();
+        rmem.set(&vec![8192, 262144]).unwrap();
+        assert_eq!(rmem.get().unwrap(), [8192, 262144]);
+
+        assert!(matches!(
+            TypedCtl::<u32, _>::from_ctl(mock.ctl("kernel.ostype").unwrap()),
+            Err(SysctlError::TypeMismatch {
+                found: CtlType::String,
+                ..
+            })
+        ));
+        assert!(TypedCtl::<String, _>::from_ctl(mock.ctl("vm.swappiness").unwrap()).is_err());
+
+        mock.insert(
+            "dev.cpu.0.temperature",
+            MockEntry::new(CtlValue::Int(3181)).with_fmt("IK"),
+        );
+        assert!(matches!(
+            TypedCtl::<i32, _>::from_ctl(mock.ctl("dev.cpu.0.temperature").unwrap()),
+            Err(SysctlError::TypeMismatch {
+                found: CtlType::Int,
+                ..
+            })
+        ));
+
+        // Write-only sysctls are not checked.
+        let drop_caches = TypedCtl::<u8, _>::from_ctl(mock.ctl("vm.drop_caches").unwrap()).unwrap();
+        drop_caches.set(&3).unwrap();
+        assert_eq!(
+            mock.get("vm.drop_caches"),
+            Some(CtlValue::String("3".to_owned()))
+        );
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn typed_ctl_linux() {
+        let root = crate::sys::tests::fake_root(
+            "typed_ctl_linux",
+            &[
+                ("vm/swappiness", "60\n"),
+                ("net/ipv4/tcp_rmem", "4096\t131072\t6291456\n"),
+                ("kernel/ostype", "Linux\n"),
+            ],
+        );
+        let ctl = |name| crate::Ctl::new_in(&root, name).unwrap();
+
+        let swappiness = TypedCtl::<u32, _>::from_ctl(ctl("vm.swappiness")).unwrap();
+        assert_eq!(swappiness.get().unwrap(), 60);
+        swappiness.set(&30).unwrap();
+        assert_eq!(swappiness.get().unwrap(), 30);
+
+        let rmem = TypedCtl::<Vec<u64>, _>::from_ctl(ctl("net.ipv4.tcp_rmem")).unwrap();
+        assert_eq!(rmem.get().unwrap(), [4096, 131072, 6291456]);
+
+        assert!(matches!(
+            TypedCtl::<bool, _>::from_ctl(ctl("kernel.ostype")),
+            Err(SysctlError::TypeMismatch { .. })
+        ));
+    }
+}
@@ -3,6 +3,8 @@
 use super::funcs::*;
 use crate::ctl_error::SysctlError;
 use crate::ctl_flags::CtlFlags;
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos", target_os = "visionos"))]
+use crate::ctl_format::CtlFormat;
 use crate::ctl_info::CtlInfo;
 use crate::ctl_type::CtlType;
 use crate::ctl_value::CtlValue;
@@ -78,30 +80,11 @@ impl Sysctl for Ctl {
         match self {
             Ctl::Oid(oid) => {
                 let info = oidfmt(oid)?;
-
-                Ok(match info.ctl_type {
-                    CtlType::Int => match info.fmt.as_str() {
-                        "I" => CtlType::Int,
-                        "IU" => CtlType::Uint,
-                        "L" => CtlType::Long,
-                        "LU" => CtlType::Ulong,
-                        _ => return Err(SysctlError::MissingImplementation),
-                    },
-                    ctl_type => ctl_type,
-                })
-            }
-            Ctl::Name(_, ctl_type, fmt) => {
-                Ok(match ctl_type {
-                    CtlType::Int => match fmt.as_str() {
-                        "I" => CtlType::Int,
-                        "IU" => CtlType::Uint,
-                        "L" => CtlType::Long,
-                        "LU" => CtlType::Ulong,
-                        _ => return Err(SysctlError::MissingImplementation),
-                    },
-                    ctl_type => *ctl_type,
-                })
+                resolve_type(info.ctl_type, &info.format())
+                    .ok_or(SysctlError::MissingImplementation)
             }
+            Ctl::Name(_, ctl_type, fmt) => resolve_type(*ctl_type, &CtlFormat::parse(fmt))
+                .ok_or(SysctlError::MissingImplementation),
         }
     }
 
@@ -3,6 +3,8 @@
 use byteorder::{ByteOrder, WriteBytesExt};
 use crate::consts::*;
 use crate::ctl_error::*;
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos", target_os = "visionos"))]
+use crate::ctl_format::*;
 use crate::ctl_info::*;
 use crate::ctl_type::*;
 use crate::ctl_value::*;
@@ -227,6 +229,19 @@ pub fn value_oid(oid: &[i32]) -> Result<CtlValue, SysctlError> {
     }
 }
 
+/// Return the `CtlType` of a sysctl from the type and format reported by
+/// `oidfmt`. macOS reports every integer as `CtlType::Int`, so their width
+/// and signedness come from the format. Returns `None` for integers with a
+/// format that is not an integer one.
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos", target_os = "visionos"))]
+pub(crate) fn resolve_type(ctl_type: CtlType, fmt: &CtlFormat) -> Option<CtlType> {
+    match (ctl_type, fmt) {
+        (CtlType::Int, CtlFormat::Integer { .. }) => fmt.ctl_type(),
+        (CtlType::Int, _) => None,
+        (ctl_type, _) => Some(ctl_type),
+    }
+}
+
 #[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos", target_os = "visionos"))]
 pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
     let info: CtlInfo = oidfmt(&oid)?;
@@ -236,6 +251,11 @@ pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
         return Err(SysctlError::NoReadAccess);
     }
 
+    let ctl_type = match resolve_type(info.ctl_type, &info.format()) {
+        Some(ctl_type) => ctl_type,
+        None => return Ok(CtlValue::None),
+    };
+
     // First get size of value in bytes
     let mut val_len = 0;
     let ret = unsafe {
@@ -255,7 +275,7 @@ pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
     // If the length reported is shorter than the type we will convert it into,
     // byteorder::LittleEndian::read_* will panic. Therefore, expand the value length to at
     // Least the size of the value.
-    let val_minsize = std::cmp::max(val_len, info.ctl_type.min_type_size());
+    let val_minsize = std::cmp::max(val_len, ctl_type.min_type_size());
 
     // Then get value
     let mut val: Vec<libc::c_uchar> = vec![0; val_minsize];
@@ -284,16 +304,10 @@ pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
     }
 
     // Wrap in Enum and return
-    match info.ctl_type {
+    match ctl_type {
         CtlType::None => Ok(CtlValue::None),
         CtlType::Node => Ok(CtlValue::Node(val)),
-        CtlType::Int => match info.fmt.as_str() {
-            "I" => Ok(CtlValue::Int(byteorder::LittleEndian::read_i32(&val))),
-            "IU" => Ok(CtlValue::Uint(byteorder::LittleEndian::read_u32(&val))),
-            "L" => Ok(CtlValue::Long(byteorder::LittleEndian::read_i64(&val))),
-            "LU" => Ok(CtlValue::Ulong(byteorder::LittleEndian::read_u64(&val))),
-            _ => Ok(CtlValue::None),
-        }
+        CtlType::Int => Ok(CtlValue::Int(byteorder::LittleEndian::read_i32(&val))),
         CtlType::String => match val.len() {
             0 => Ok(CtlValue::String("".to_string())),
             l => std::str::from_utf8(&val[..l - 1])
@@ -312,6 +326,7 @@ pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
         CtlType::S16 => Ok(CtlValue::S16(byteorder::LittleEndian::read_i16(&val))),
         CtlType::S32 => Ok(CtlValue::S32(byteorder::LittleEndian::read_i32(&val))),
         CtlType::U32 => Ok(CtlValue::U32(byteorder::LittleEndian::read_u32(&val))),
+        CtlType::List => Err(SysctlError::MissingImplementation),
     }
 }
 
@@ -405,6 +420,10 @@ pub fn value_oid_as<T>(oid: &mut Vec<i32>) -> Result<Box<T>, SysctlError> {
 
 #[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos", target_os = "visionos"))]
 pub fn value_name(name: &str, ctl_type: CtlType, fmt: &str) -> Result<CtlValue, SysctlError> {
+    let ctl_type = match resolve_type(ctl_type, &CtlFormat::parse(fmt)) {
+        Some(ctl_type) => ctl_type,
+        None => return Ok(CtlValue::None),
+    };
     let name = CString::new(name)?;
 
     // First get size of value in bytes
@@ -456,13 +475,7 @@ pub fn value_name(name: &str, ctl_type: CtlType, fmt: &str) -> Result<CtlValue,
     match ctl_type {
         CtlType::None => Ok(CtlValue::None),
         CtlType::Node => Ok(CtlValue::Node(val)),
-        CtlType::Int => match fmt {
-            "I" => Ok(CtlValue::Int(byteorder::LittleEndian::read_i32(&val))),
-            "IU" => Ok(CtlValue::Uint(byteorder::LittleEndian::read_u32(&val))),
-            "L" => Ok(CtlValue::Long(byteorder::LittleEndian::read_i64(&val))),
-            "LU" => Ok(CtlValue::Ulong(byteorder::LittleEndian::read_u64(&val))),
-            _ => Ok(CtlValue::None),
-        }
+        CtlType::Int => Ok(CtlValue::Int(byteorder::LittleEndian::read_i32(&val))),
         CtlType::String => match val.len() {
             0 => Ok(CtlValue::String("".to_string())),
             l => std::str::from_utf8(&val[..l - 1])
@@ -481,6 +494,7 @@ pub fn value_name(name: &str, ctl_type: CtlType, fmt: &str) -> Result<CtlValue,
         CtlType::S16 => Ok(CtlValue::S16(byteorder::LittleEndian::read_i16(&val))),
         CtlType::S32 => Ok(CtlValue::S32(byteorder::LittleEndian::read_i32(&val))),
         CtlType::U32 => Ok(CtlValue::U32(byteorder::LittleEndian::read_u32(&val))),
+        CtlType::List => Err(SysctlError::MissingImplementation),
     }
 }
 
@@ -604,16 +618,8 @@ pub fn set_oid_value(oid: &mut Vec<libc::c_int>, value: CtlValue) -> Result<CtlV
     let ctl_type = CtlType::from(&value);
 
     // Get the correct ctl type based on the format string
-    let info_ctl_type = match info.ctl_type {
-        CtlType::Int => match info.fmt.as_str() {
-            "I" => CtlType::Int,
-            "IU" => CtlType::Uint,
-            "L" => CtlType::Long,
-            "LU" => CtlType::Ulong,
-            _ => return Err(SysctlError::MissingImplementation),
-        }
-        ctl_type => ctl_type,
-    };
+    let info_ctl_type = resolve_type(info.ctl_type, &info.format())
+        .ok_or(SysctlError::MissingImplementation)?;
 
     assert_eq!(
         info_ctl_type, ctl_type,
@@ -653,16 +659,8 @@ pub fn set_name_value(
     let ctl_type = CtlType::from(&value);
 
     // Get the correct ctl type based on the format string
-    let info_ctl_type = match info_ctl_type {
-        CtlType::Int => match fmt {
-            "I" => CtlType::Int,
-            "IU" => CtlType::Uint,
-            "L" => CtlType::Long,
-            "LU" => CtlType::Ulong,
-            _ => return Err(SysctlError::MissingImplementation),
-        }
-        ctl_type => ctl_type,
-    };
+    let info_ctl_type = resolve_type(info_ctl_type, &CtlFormat::parse(fmt))
+        .ok_or(SysctlError::MissingImplementation)?;
 
     assert_eq!(
         info_ctl_type, ctl_type,
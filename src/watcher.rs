@@ -0,0 +1,334 @@
+// watcher.rs
+
+use crate::ctl_error::SysctlError;
+use crate::ctl_value::CtlValue;
+use crate::traits::Sysctl;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The sysctls the Linux kernel notifies pollers about when they change.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+const POLLABLE: &[&str] = &["kernel.domainname", "kernel.hostname"];
+
+/// A change of a watched sysctl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub name: String,
+    pub old: CtlValue,
+    pub new: CtlValue,
+}
+
+struct Watched {
+    ctl: crate::Ctl,
+    name: String,
+    value: CtlValue,
+    // An open file to wait on with poll(2), for sysctls the kernel
+    // notifies pollers about.
+    file: Option<std::fs::File>,
+}
+
+impl Watched {
+    // Re-read the value and return an event if it changed. Sysctls that
+    // cannot be read any more are ignored.
+    fn check(&mut self) -> Option<ChangeEvent> {
+        let new = self.ctl.value().ok()?;
+        if new == self.value {
+            return None;
+        }
+        let old = std::mem::replace(&mut self.value, new.clone());
+        Some(ChangeEvent {
+            name: self.name.clone(),
+            old,
+            new,
+        })
+    }
+}
+
+/// Watches sysctls for changes made by other processes.
+///
+/// On Linux, `kernel.hostname` and `kernel.domainname` are waited on with
+/// `poll(2)`, which reports their changes right away. All other sysctls
+/// are read again every interval, one second by default, and compared with
+/// their previous values.
+///
+/// # Example
+/// ```no_run
+/// # use sysctl::Sysctl;
+/// let ctl = sysctl::Ctl::new("net.ipv4.ip_forward").unwrap();
+/// let watcher = sysctl::Watcher::new([ctl]).unwrap();
+/// for event in watcher {
+///     let event = event.unwrap();
+///     println!("{} changed from {} to {}", event.name, event.old, event.new);
+/// }
+/// ```
+pub struct Watcher {
+    watched: Vec<Watched>,
+    interval: Duration,
+    last_check: Instant,
+    pending: VecDeque<ChangeEvent>,
+}
+
+impl Watcher {
+    /// Watch the given sysctls. Sysctls whose value cannot be read, such
+    /// as write-only ones, are left out.
+    pub fn new<I: IntoIterator<Item = crate::Ctl>>(ctls: I) -> Result<Self, SysctlError> {
+        let mut watched = vec![];
+        for ctl in ctls {
+            let value = match ctl.value() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let name = ctl.name()?;
+            watched.push(Watched {
+                file: poll_file(&ctl, &name),
+                ctl,
+                name,
+                value,
+            });
+        }
+        Ok(Watcher {
+            watched,
+            interval: Duration::from_secs(1),
+            last_check: Instant::now(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Watch all sysctls below `node`. Sysctls added to the subtree later
+    /// are not watched.
+    pub fn below(node: crate::Ctl) -> Result<Self, SysctlError> {
+        let ctls = crate::CtlIter::below(node).collect::<Result<Vec<_>, _>>()?;
+        Watcher::new(ctls)
+    }
+
+    /// Set how often sysctls that cannot be polled are read.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the number of watched sysctls.
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    /// Read all watched sysctls and return the changes since the last
+    /// check, without waiting.
+    pub fn check(&mut self) -> Vec<ChangeEvent> {
+        self.last_check = Instant::now();
+        self.watched.iter_mut().filter_map(Watched::check).collect()
+    }
+
+    /// Wait until at least one watched sysctl changes and return the
+    /// changes. Returns no changes right away if no sysctls are watched.
+    pub fn wait(&mut self) -> Result<Vec<ChangeEvent>, SysctlError> {
+        self.wait_until(None)
+    }
+
+    /// Wait until at least one watched sysctl changes or `timeout` has
+    /// passed. Returns no changes on timeout, and right away if no sysctls
+    /// are watched.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Result<Vec<ChangeEvent>, SysctlError> {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    fn wait_until(&mut self, deadline: Option<Instant>) -> Result<Vec<ChangeEvent>, SysctlError> {
+        if self.watched.is_empty() {
+            return Ok(vec![]);
+        }
+        let interval_watched = self.watched.iter().any(|w| w.file.is_none());
+        loop {
+            let now = Instant::now();
+            let next_check = interval_watched.then(|| self.last_check + self.interval);
+            let wake = match (next_check, deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            let mut events = vec![];
+            for i in self.poll(wake.map(|w| w.saturating_duration_since(now)))? {
+                events.extend(self.watched[i].check());
+            }
+            let now = Instant::now();
+            if next_check.is_some_and(|c| now >= c) {
+                self.last_check = now;
+                for w in self.watched.iter_mut().filter(|w| w.file.is_none()) {
+                    events.extend(w.check());
+                }
+            }
+            if !events.is_empty() || deadline.is_some_and(|d| now >= d) {
+                return Ok(events);
+            }
+        }
+    }
+
+    // Wait for the pollable sysctls for at most `timeout`, and return the
+    // indices of those that changed.
+    fn poll(&self, timeout: Option<Duration>) -> Result<Vec<usize>, SysctlError> {
+        use std::os::fd::AsRawFd;
+
+        let (indices, mut fds): (Vec<usize>, Vec<libc::pollfd>) = self
+            .watched
+            .iter()
+            .enumerate()
+            .filter_map(|(i, w)| {
+                let fd = w.file.as_ref()?.as_raw_fd();
+                Some((
+                    i,
+                    libc::pollfd {
+                        fd,
+                        events: libc::POLLPRI,
+                        revents: 0,
+                    },
+                ))
+            })
+            .unzip();
+        let timeout = match timeout {
+            Some(t) => t.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if ret < 0 {
+            let e = std::io::Error::last_os_error();
+            return match e.kind() {
+                std::io::ErrorKind::Interrupted => Ok(vec![]),
+                _ => Err(e.into()),
+            };
+        }
+        Ok(indices
+            .into_iter()
+            .zip(fds)
+            .filter(|(_, fd)| fd.revents & (libc::POLLPRI | libc::POLLERR) != 0)
+            .map(|(i, _)| i)
+            .collect())
+    }
+}
+
+// Open the file to poll for `ctl`, if the kernel notifies pollers about
+// its changes.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn poll_file(ctl: &crate::Ctl, name: &str) -> Option<std::fs::File> {
+    use crate::sys::funcs::PROC_SYS;
+
+    if ctl.netns().is_some() || ctl.root() != PROC_SYS || !POLLABLE.contains(&name) {
+        return None;
+    }
+    std::fs::File::open(ctl.path()).ok()
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn poll_file(_ctl: &crate::Ctl, _name: &str) -> Option<std::fs::File> {
+    None
+}
+
+/// Iterating over a `Watcher` waits for changes and yields them one by
+/// one, forever. The iterator is empty if no sysctls are watched.
+impl Iterator for Watcher {
+    type Item = Result<ChangeEvent, SysctlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.watched.is_empty() {
+                return None;
+            }
+            match self.wait() {
+                Ok(events) => self.pending.extend(events),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+#[cfg(all(test, any(target_os = "android", target_os = "linux")))]
+mod tests {
+    use super::Watcher;
+    use crate::{CtlValue, Sysctl};
+    use std::time::Duration;
+
+    #[test]
+    fn watcher_interval() {
+        let root = crate::sys::tests::fake_root(
+            "watcher_interval",
+            &[
+                ("net/ipv4/ip_forward", "0\n"),
+                ("net/ipv4/ip_default_ttl", "64\n"),
+            ],
+        );
+        let net = crate::Ctl::new_in(&root, "net").expect("Ctl::new_in");
+        let mut watcher = Watcher::below(net)
+            .expect("Watcher::below")
+            .with_interval(Duration::from_millis(10));
+        assert_eq!(watcher.len(), 2);
+        assert!(watcher.check().is_empty());
+        assert!(
+            watcher
+                .wait_timeout(Duration::from_millis(30))
+                .expect("wait_timeout")
+                .is_empty()
+        );
+
+        let path = format!("{}/net/ipv4/ip_forward", root);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            std::fs::write(path, "1\n").expect("write");
+        });
+        let events = watcher.wait().expect("wait");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "net.ipv4.ip_forward");
+        assert_eq!(events[0].old, CtlValue::String("0".to_owned()));
+        assert_eq!(events[0].new, CtlValue::String("1".to_owned()));
+    }
+
+    #[test]
+    fn watcher_empty() {
+        let mut watcher = Watcher::new([]).expect("Watcher::new");
+        assert!(watcher.is_empty());
+        assert!(watcher.wait().expect("wait").is_empty());
+        assert!(watcher.next().is_none());
+    }
+
+    #[test]
+    fn watcher_poll_hostname() {
+        // Change the hostname in a UTS namespace of our own, and make sure
+        // the change is seen long before the interval has passed.
+        let events = std::thread::spawn(|| {
+            if unsafe { libc::unshare(libc::CLONE_NEWUTS) } < 0 {
+                return None;
+            }
+            let ctl = crate::Ctl::new("kernel.hostname").expect("Ctl::new");
+            let mut watcher = Watcher::new([ctl])
+                .expect("Watcher::new")
+                .with_interval(Duration::from_secs(3600));
+            assert!(watcher.watched[0].file.is_some());
+
+            std::thread::spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                let name = b"sysctl-rs-watcher";
+                unsafe { libc::sethostname(name.as_ptr() as *const libc::c_char, name.len()) };
+            });
+            Some(
+                watcher
+                    .wait_timeout(Duration::from_secs(10))
+                    .expect("wait_timeout"),
+            )
+        })
+        .join()
+        .expect("join");
+
+        if let Some(events) = events {
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].name, "kernel.hostname");
+            assert_eq!(
+                events[0].new,
+                CtlValue::String("sysctl-rs-watcher".to_owned())
+            );
+        }
+    }
+}
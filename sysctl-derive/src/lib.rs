@@ -0,0 +1,143 @@
+//! Derive macro for the `sysctl` crate.
+//!
+//! Enable the `derive` feature of `sysctl` instead of depending on this
+//! crate directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
+
+/// Bind the fields of a struct to sysctls.
+///
+/// Every field needs a `#[sysctl(name = "...")]` attribute naming the
+/// sysctl it is read from and applied to. Field types must implement
+/// `sysctl::FromSysctl` and `sysctl::ToSysctl`.
+///
+/// The derive generates `read()` and `apply()` for the system sysctls and
+/// `read_from()` and `apply_to()` for a `sysctl::Backend`. They return a
+/// `sysctl::GroupError` listing every field that failed.
+#[proc_macro_derive(Sysctl, attributes(sysctl))]
+pub fn derive_sysctl(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "#[derive(Sysctl)] requires a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[derive(Sysctl)] requires a struct",
+            ));
+        }
+    };
+
+    let mut idents = vec![];
+    let mut types = vec![];
+    let mut names = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        idents.push(ident);
+        types.push(&field.ty);
+        names.push(sysctl_name(field)?);
+    }
+    let labels: Vec<String> = idents.iter().map(|i| i.to_string()).collect();
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Read all fields from the system sysctls.
+            pub fn read() -> ::std::result::Result<Self, ::sysctl::GroupError> {
+                Self::read_from(&::sysctl::SystemBackend)
+            }
+
+            /// Read all fields from the sysctls of `backend`.
+            pub fn read_from<B: ::sysctl::Backend>(
+                backend: &B,
+            ) -> ::std::result::Result<Self, ::sysctl::GroupError> {
+                let mut errors = ::std::vec::Vec::new();
+                #(
+                    let #idents = ::sysctl::GroupError::record(
+                        &mut errors,
+                        #labels,
+                        #names,
+                        backend
+                            .ctl(#names)
+                            .and_then(::sysctl::TypedCtl::<#types, B::Ctl>::from_ctl)
+                            .and_then(|ctl| ctl.get()),
+                    );
+                )*
+                match (#(#idents,)*) {
+                    (#(::std::option::Option::Some(#idents),)*) => {
+                        ::std::result::Result::Ok(Self { #(#idents,)* })
+                    }
+                    _ => ::std::result::Result::Err(::sysctl::GroupError { errors }),
+                }
+            }
+
+            /// Write all fields to the system sysctls.
+            pub fn apply(&self) -> ::std::result::Result<(), ::sysctl::GroupError> {
+                self.apply_to(&::sysctl::SystemBackend)
+            }
+
+            /// Write all fields to the sysctls of `backend`. Fields are
+            /// written in order, and a failure does not stop the remaining
+            /// fields from being written.
+            pub fn apply_to<B: ::sysctl::Backend>(
+                &self,
+                backend: &B,
+            ) -> ::std::result::Result<(), ::sysctl::GroupError> {
+                let mut errors = ::std::vec::Vec::new();
+                #(
+                    ::sysctl::GroupError::record(
+                        &mut errors,
+                        #labels,
+                        #names,
+                        backend
+                            .ctl(#names)
+                            .and_then(::sysctl::TypedCtl::<#types, B::Ctl>::from_ctl)
+                            .and_then(|ctl| ctl.set(&self.#idents)),
+                    );
+                )*
+                match errors.is_empty() {
+                    true => ::std::result::Result::Ok(()),
+                    false => ::std::result::Result::Err(::sysctl::GroupError { errors }),
+                }
+            }
+        }
+    })
+}
+
+// Returns the value of the `#[sysctl(name = "...")]` attribute of `field`.
+fn sysctl_name(field: &syn::Field) -> syn::Result<LitStr> {
+    let mut name = None;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("sysctl")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown sysctl attribute, expected `name`"))
+            }
+        })?;
+    }
+    name.ok_or_else(|| {
+        syn::Error::new_spanned(field, "missing #[sysctl(name = \"...\")] attribute")
+    })
+}